@@ -0,0 +1,283 @@
+use crate::config::ApiConfig;
+use crate::events::Event;
+use crate::models::{LogLine, Service, ServiceStatus};
+use anyhow::{Context, Result};
+use axum::extract::{Path as UrlPath, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+/// A request to start, stop or restart a service, handled by whichever
+/// loop owns the services (the TUI or the daemon).
+#[derive(Debug, Clone)]
+pub enum Control {
+    Start(String),
+    Stop(String),
+    Restart(String),
+}
+
+impl Control {
+    pub fn service(&self) -> &str {
+        match self {
+            Control::Start(name) | Control::Stop(name) | Control::Restart(name) => name,
+        }
+    }
+}
+
+/// A service as listed by the API.
+#[derive(Debug, Clone, Serialize)]
+struct ServiceInfo {
+    #[serde(flatten)]
+    service: Service,
+    status: ServiceStatus,
+}
+
+/// `ServiceStatus` and `ServiceLog` events as sent on `/api/events`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ApiEvent {
+    Status { service: String, status: ServiceStatus },
+    Log { line: LogLine },
+}
+
+/// Shared between the HTTP handlers and the loop feeding them events.
+pub struct Hub {
+    services: Mutex<Vec<ServiceInfo>>,
+    events: broadcast::Sender<ApiEvent>,
+    control: UnboundedSender<Event>,
+    token: String,
+}
+
+impl Hub {
+    /// Pass a service event on to API clients.
+    pub fn publish(&self, event: &Event) {
+        let event = match event {
+            Event::ServiceStatus(name, status) => {
+                if let Some(info) = self.services.lock().unwrap().iter_mut().find(|i| i.service.name == *name) {
+                    info.status = status.clone();
+                }
+                ApiEvent::Status { service: name.clone(), status: status.clone() }
+            }
+            Event::ServiceLog(line) => ApiEvent::Log { line: line.clone() },
+            _ => return,
+        };
+        let _ = self.events.send(event);
+    }
+
+    /// Replace the service list after a rescan.
+    pub fn set_services(&self, services: &[Service]) {
+        *self.services.lock().unwrap() = service_infos(services);
+    }
+}
+
+fn service_infos(services: &[Service]) -> Vec<ServiceInfo> {
+    services
+        .iter()
+        .map(|s| ServiceInfo {
+            // A copy without the runtime state, which isn't serialized anyway.
+            service: Service::new(s.name.clone(), s.path.clone(), s.manifest.clone(), s.project_type.clone(), s.command.clone()),
+            status: s.status.clone(),
+        })
+        .collect()
+}
+
+/// A token nobody else can guess, from the OS's random source.
+fn generate_token() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    use std::io::Read;
+
+    let mut bytes = [0u8; 16];
+    let random = std::fs::File::open("/dev/urandom").and_then(|mut f| f.read_exact(&mut bytes));
+    if random.is_err() {
+        // Randomly keyed by the standard library on every platform.
+        for chunk in bytes.chunks_mut(8) {
+            let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+            hasher.write_u128(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos());
+            chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+        }
+    }
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Write the URL and token to `.devrunner/api.json` for clients to pick up,
+/// readable only by the current user.
+fn write_discovery_file(root_dir: &Path, url: &str, token: &str) -> Result<()> {
+    let dir = root_dir.join(".devrunner");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("api.json");
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(&path)?;
+    // `mode` only applies when the file is created; tighten one left behind.
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    serde_json::to_writer_pretty(file, &serde_json::json!({ "url": url, "token": token }))?;
+    Ok(())
+}
+
+/// Start the HTTP API on 127.0.0.1. Start, stop and restart requests are
+/// sent to `control` as `Event::Control`; events passed to `Hub::publish`
+/// are streamed to clients.
+pub async fn serve(config: &ApiConfig, root_dir: &Path, services: &[Service], control: UnboundedSender<Event>) -> Result<Arc<Hub>> {
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", config.port))
+        .await
+        .with_context(|| format!("API port {} is unavailable", config.port))?;
+    let token = config.token.clone().unwrap_or_else(generate_token);
+    write_discovery_file(root_dir, &format!("http://127.0.0.1:{}", config.port), &token)?;
+
+    let (events, _) = broadcast::channel(4096);
+    let hub = Arc::new(Hub {
+        services: Mutex::new(service_infos(services)),
+        events,
+        control,
+        token,
+    });
+    let app = Router::new()
+        .route("/api/services", get(list_services))
+        .route("/api/services/{name}", get(get_service))
+        .route("/api/services/{name}/{action}", post(control_service))
+        .route("/api/events", get(stream_events))
+        .layer(middleware::from_fn_with_state(hub.clone(), authorize))
+        .with_state(hub.clone());
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    Ok(hub)
+}
+
+/// Accept `Authorization: Bearer <token>`, or `?token=` for clients such as
+/// `EventSource` that can't set headers.
+async fn authorize(State(hub): State<Arc<Hub>>, Query(query): Query<HashMap<String, String>>, request: Request, next: Next) -> Response {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if bearer.or(query.get("token").map(String::as_str)).is_some_and(|given| tokens_match(given, &hub.token)) {
+        next.run(request).await
+    } else {
+        error(StatusCode::UNAUTHORIZED, "Missing or invalid token")
+    }
+}
+
+/// Compare a presented token with ours in time that doesn't depend on how
+/// much of it is right.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    let (given, expected) = (given.as_bytes(), expected.as_bytes());
+    let difference = given.iter().zip(expected).fold(0, |acc, (a, b)| acc | (a ^ b));
+    given.len() == expected.len() && std::hint::black_box(difference) == 0
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+async fn list_services(State(hub): State<Arc<Hub>>) -> Json<Vec<ServiceInfo>> {
+    Json(hub.services.lock().unwrap().clone())
+}
+
+async fn get_service(State(hub): State<Arc<Hub>>, UrlPath(name): UrlPath<String>) -> Response {
+    match hub.services.lock().unwrap().iter().find(|i| i.service.name == name) {
+        Some(info) => Json(info.clone()).into_response(),
+        None => error(StatusCode::NOT_FOUND, "Unknown service"),
+    }
+}
+
+#[derive(Deserialize)]
+struct ControlPath {
+    name: String,
+    action: String,
+}
+
+async fn control_service(State(hub): State<Arc<Hub>>, UrlPath(path): UrlPath<ControlPath>) -> Response {
+    if !hub.services.lock().unwrap().iter().any(|i| i.service.name == path.name) {
+        return error(StatusCode::NOT_FOUND, "Unknown service");
+    }
+    let control = match path.action.as_str() {
+        "start" => Control::Start(path.name),
+        "stop" => Control::Stop(path.name),
+        "restart" => Control::Restart(path.name),
+        _ => return error(StatusCode::NOT_FOUND, "Unknown action; use start, stop or restart"),
+    };
+    if hub.control.send(Event::Control(control)).is_err() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Shutting down");
+    }
+    // The outcome arrives as status events.
+    (StatusCode::ACCEPTED, Json(serde_json::json!({ "ok": true }))).into_response()
+}
+
+/// Every service's current status, then status changes and log lines as
+/// they happen.
+async fn stream_events(State(hub): State<Arc<Hub>>) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let updates = hub.events.subscribe();
+    let current: Vec<ApiEvent> = hub
+        .services
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|i| ApiEvent::Status { service: i.service.name.clone(), status: i.status.clone() })
+        .collect();
+    // Clients too slow to keep up miss events rather than stall everyone.
+    let stream = tokio_stream::iter(current)
+        .chain(BroadcastStream::new(updates).filter_map(Result::ok))
+        .map(|event| {
+            let kind = match event {
+                ApiEvent::Status { .. } => "status",
+                ApiEvent::Log { .. } => "log",
+            };
+            Ok(SseEvent::default().event(kind).json_data(&event).unwrap_or_default())
+        });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        assert!(tokens_match("0123abcd", "0123abcd"));
+        assert!(!tokens_match("0123abce", "0123abcd"));
+        assert!(!tokens_match("0123abc", "0123abcd"));
+        assert!(!tokens_match("0123abcd0", "0123abcd"));
+        assert!(!tokens_match("", "0123abcd"));
+    }
+
+    #[test]
+    fn generated_tokens_are_random_hex() {
+        let token = generate_token();
+        assert_eq!(token.len(), 32);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, generate_token());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn discovery_file_is_private_even_when_it_existed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir().expect("temp dir");
+        let path = root.path().join(".devrunner").join("api.json");
+        std::fs::create_dir_all(path.parent().expect("parent")).expect("create dir");
+        std::fs::write(&path, "{}").expect("write old file");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).expect("loosen");
+        write_discovery_file(root.path(), "http://127.0.0.1:1", "secret").expect("write");
+        assert_eq!(std::fs::metadata(&path).expect("metadata").permissions().mode() & 0o777, 0o600);
+        let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).expect("read")).expect("json");
+        assert_eq!(written["token"], "secret");
+    }
+}
@@ -1,66 +1,1719 @@
+use crate::api::{self, Control};
+use crate::archive::{self, LogArchive, RunInfo};
+use crate::buffer::LogBuffer;
+use crate::clipboard;
+use crate::config::{self, AppConfig};
+#[cfg(unix)]
+use crate::daemon::{self, Request};
 use crate::events::Event;
-use crate::models::{Service, ServiceStatus};
+use crate::keys::{Action, Keymap};
+use crate::logparse;
+use crate::metrics;
+use crate::limits;
+use crate::models::{LogLevel, LogLine, Service, ServiceStatus};
+use crate::palette::{self, Command, Palette, Target};
+use crate::ports::{self, Holder};
 use crate::process::ProcessManager;
+use crate::reload::{self, ChangeKind, ServiceChange};
+use crate::search::{self, LogFilter};
+use crate::state::{self, UiState};
+use crate::tree::{self, SidebarRow};
 use crate::ui;
+use crate::scanner;
+use crate::watch::{self, ProjectWatcher, ServiceWatcher};
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event as CEvent, KeyCode, KeyEventKind},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event as CEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use ratatui::{backend::CrosstermBackend, Terminal};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Position, Rect},
+    Terminal,
+};
+use std::collections::{HashMap, HashSet};
 use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use sysinfo::System;
+use unicode_width::UnicodeWidthChar;
+
+/// Lines scrolled per mouse wheel notch.
+const WHEEL_STEP: usize = 3;
+
+/// Maximum number of split log panes.
+pub const MAX_PANES: usize = 4;
+
+/// Scroll state of a single service's log pane.
+///
+/// Positions are measured in wrapped rows, so `total_rows` and `page_height`
+/// are refreshed by `ui::draw` every frame for the current pane width.
+#[derive(Debug, Clone)]
+pub struct LogView {
+    pub follow: bool,
+    pub top_row: usize,
+    pub new_lines: usize,
+    pub total_rows: usize,
+    pub page_height: usize,
+    pub filter: LogFilter,
+    /// Sequence number of the line holding the current search match.
+    pub cursor: Option<u64>,
+    /// Sequence number of a line to scroll into view on the next draw.
+    pub jump_to: Option<u64>,
+    /// First visible line and the wrapped row within it, recorded at draw
+    /// time so a paused view stays put while old lines are evicted.
+    pub anchor: Option<(u64, usize)>,
+    /// `top_row` as of the last draw, used to tell user scrolling apart
+    /// from buffer eviction.
+    pub drawn_top: usize,
+    /// Last line visible at the last draw.
+    pub last_visible: Option<u64>,
+    /// Text of each row on screen at the last draw, flagged when it
+    /// continues the wrapped line above; used to copy mouse selections.
+    pub visible_rows: Vec<(String, bool)>,
+    /// Wrapped row counts of the lines shown, kept up to date by the draw.
+    pub rows: ui::RowIndex,
+}
+
+impl Default for LogView {
+    fn default() -> Self {
+        Self {
+            follow: true,
+            top_row: 0,
+            new_lines: 0,
+            total_rows: 0,
+            page_height: 0,
+            filter: LogFilter::default(),
+            cursor: None,
+            jump_to: None,
+            anchor: None,
+            drawn_top: 0,
+            last_visible: None,
+            visible_rows: Vec::new(),
+            rows: ui::RowIndex::default(),
+        }
+    }
+}
+
+impl LogView {
+    fn bottom(&self) -> usize {
+        self.total_rows.saturating_sub(self.page_height)
+    }
+
+    pub fn scroll_up(&mut self, rows: usize) {
+        if self.follow {
+            self.top_row = self.bottom();
+            self.follow = false;
+        }
+        self.top_row = self.top_row.saturating_sub(rows);
+    }
+
+    pub fn scroll_down(&mut self, rows: usize) {
+        if self.follow {
+            return;
+        }
+        self.top_row += rows;
+        if self.top_row >= self.bottom() {
+            self.end();
+        }
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll_up(self.page_height.max(1));
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_down(self.page_height.max(1));
+    }
+
+    pub fn home(&mut self) {
+        self.follow = false;
+        self.top_row = 0;
+    }
+
+    /// Jump back to the tail and resume following new output.
+    pub fn end(&mut self) {
+        self.follow = true;
+        self.new_lines = 0;
+        self.top_row = self.bottom();
+    }
+
+    pub fn on_new_line(&mut self) {
+        if !self.follow {
+            self.new_lines += 1;
+        }
+    }
+
+    fn jump(&mut self, line: u64) {
+        self.follow = false;
+        self.cursor = Some(line);
+        self.jump_to = Some(line);
+    }
+}
+
+/// Order of the sidebar: the project tree, or a flat list by usage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortMode {
+    Tree,
+    Cpu,
+    Memory,
+}
+
+impl SortMode {
+    fn cycle(self) -> Self {
+        match self {
+            SortMode::Tree => SortMode::Cpu,
+            SortMode::Cpu => SortMode::Memory,
+            SortMode::Memory => SortMode::Tree,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Tree => "tree",
+            SortMode::Cpu => "by CPU",
+            SortMode::Memory => "by memory",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputMode {
+    Normal,
+    Search,
+    Filter,
+}
+
+/// A past run of a service loaded read-only from the log archive.
+pub struct ArchiveView {
+    pub service: String,
+    pub file_name: String,
+    pub logs: LogBuffer,
+}
+
+pub enum Popup {
+    /// Persisted runs of a service to pick from.
+    Runs {
+        service: String,
+        runs: Vec<RunInfo>,
+        selected: usize,
+    },
+    /// Fuzzy finder over services or commands.
+    Palette(Palette),
+    /// Key bindings grouped by context.
+    Help {
+        scroll: u16,
+    },
+    /// A single log line expanded over several lines.
+    Detail {
+        title: String,
+        lines: Vec<String>,
+        scroll: u16,
+    },
+    /// Services added, removed or modified by a config reload.
+    ConfigDiff {
+        changes: Vec<ServiceChange>,
+        scroll: u16,
+    },
+    /// The configured port of a service about to start is already taken.
+    PortConflict {
+        service: usize,
+        port: u16,
+        holder: Holder,
+        /// The DevRunner service whose process tree holds the port, if any.
+        owner: Option<String>,
+    },
+}
+
+/// Where the parts of the screen ended up at the last draw, so mouse events
+/// can be mapped back to what was under the pointer.
+#[derive(Debug, Default)]
+pub struct ScreenLayout {
+    /// The three-column area below the header.
+    pub main: Rect,
+    pub sidebar: Rect,
+    pub resources: Rect,
+    /// Log view key and inner (borderless) area of each log pane.
+    pub panes: Vec<(String, Rect)>,
+    /// First sidebar row shown, when the tree is taller than the sidebar.
+    pub sidebar_offset: usize,
+}
+
+/// What a held mouse button is doing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Drag {
+    /// Moving the border left (0) or right (1) of the log column.
+    Divider(usize),
+    /// Selecting text in a log pane.
+    Select,
+}
+
+/// Text selected with the mouse in a log pane, as (column, row) cells
+/// relative to the pane's inner area.
+#[derive(Debug, Clone, Copy)]
+pub struct Selection {
+    pub pane: usize,
+    pub start: (u16, u16),
+    pub end: (u16, u16),
+}
+
+impl Selection {
+    /// Start and end in reading order.
+    pub fn ordered(&self) -> ((u16, u16), (u16, u16)) {
+        let (a, b) = (self.start, self.end);
+        if (a.1, a.0) <= (b.1, b.0) { (a, b) } else { (b, a) }
+    }
+
+    /// The selected text: wrapped rows of one line are joined back together,
+    /// separate lines are joined with newlines.
+    fn text(&self, rows: &[(String, bool)]) -> String {
+        let ((start_col, start_row), (end_col, end_row)) = self.ordered();
+        let mut out = String::new();
+        for row in start_row..=end_row {
+            let Some((text, continued)) = rows.get(row as usize) else {
+                break;
+            };
+            if row > start_row && !continued {
+                out.push('\n');
+            }
+            let from = if row == start_row { start_col as usize } else { 0 };
+            let to = if row == end_row { end_col as usize + 1 } else { usize::MAX };
+            out.push_str(column_slice(text, from, to));
+        }
+        out
+    }
+}
+
+/// The part of `text` covering terminal columns `from..to`.
+fn column_slice(text: &str, from: usize, to: usize) -> &str {
+    let mut col = 0;
+    let mut start = text.len();
+    let mut end = text.len();
+    for (idx, ch) in text.char_indices() {
+        if col >= from && start == text.len() {
+            start = idx;
+        }
+        if col >= to {
+            end = idx;
+            break;
+        }
+        col += ch.width().unwrap_or(0);
+    }
+    &text[start.min(end)..end]
+}
+
+/// Two clicks on the same service within this interval start or stop it.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// Narrowest a side column can be dragged, in percent of the screen width.
+const MIN_COLUMN: u16 = 10;
+/// Narrowest the log column can become, in percent of the screen width.
+const MIN_LOG_COLUMN: u16 = 20;
+
+/// Key of the merged all-services view in `App::log_views`.
+pub const ALL_SERVICES_KEY: &str = "*all";
+
+/// The log buffer shown in the log pane and the key of its `LogView`: the
+/// merged view of every service when `merged` is given, otherwise the
+/// selected service's live output or an archived run of it.
+pub fn active_source<'a>(
+    services: &'a [Service],
+    selected_index: usize,
+    archive_view: &'a Option<ArchiveView>,
+    merged: Option<&'a LogBuffer>,
+) -> Option<(String, &'a LogBuffer)> {
+    if let Some(logs) = merged {
+        return Some((ALL_SERVICES_KEY.to_string(), logs));
+    }
+    let service = services.get(selected_index)?;
+    match archive_view {
+        Some(view) if view.service == service.name => {
+            Some((format!("{}#{}", view.service, view.file_name), &view.logs))
+        }
+        _ => Some((service.name.clone(), &service.logs)),
+    }
+}
+
+/// Where services run: in this process, or in a daemon the TUI is
+/// attached to, which keeps them running after the TUI quits.
+#[derive(Clone)]
+pub enum Runner {
+    Local(Arc<ProcessManager>),
+    #[cfg(unix)]
+    Daemon(daemon::Client),
+}
+
+impl Runner {
+    async fn start(&self, service: Service) {
+        match self {
+            Runner::Local(pm) => {
+                let _ = pm.spawn_service(service).await;
+            }
+            #[cfg(unix)]
+            Runner::Daemon(client) => client.request(Request::Start { service: service.name }).await,
+        }
+    }
+
+    /// Ask a running service to stop; false if it is still starting.
+    fn stop(&self, name: &str) -> bool {
+        match self {
+            Runner::Local(pm) => pm.stop_service(name),
+            #[cfg(unix)]
+            Runner::Daemon(client) => {
+                client.send(Request::Stop { service: name.to_string() });
+                true
+            }
+        }
+    }
+
+    /// Stop a running service in order to restart it. Returns whether the
+    /// caller has to start it again once it has exited; a daemon does that
+    /// itself.
+    fn restart(&self, name: &str) -> bool {
+        match self {
+            Runner::Local(pm) => pm.stop_service(name),
+            #[cfg(unix)]
+            Runner::Daemon(client) => {
+                client.send(Request::Restart { service: name.to_string() });
+                false
+            }
+        }
+    }
+
+    fn is_daemon(&self) -> bool {
+        !matches!(self, Runner::Local(_))
+    }
+
+    /// Stop local services when the TUI exits; a daemon's keep running.
+    fn shutdown(&self) {
+        if let Runner::Local(pm) = self {
+            pm.shutdown();
+        }
+    }
+}
 
 pub struct App {
     pub services: Vec<Service>,
     pub selected_index: usize,
     pub title: String,
     pub cpu_history: Vec<u64>,
+    pub log_views: HashMap<String, LogView>,
+    pub input_mode: InputMode,
+    pub input: String,
+    pub search: String,
+    pub message: Option<String>,
+    pub log_dir: PathBuf,
+    pub archive: Option<LogArchive>,
+    pub archive_view: Option<ArchiveView>,
+    pub popup: Option<Popup>,
+    /// Show every service's output interleaved in one pane.
+    pub show_all: bool,
+    pub merged_logs: LogBuffer,
+    /// Services hidden from the merged view.
+    pub muted: HashSet<String>,
+    /// Services pinned to split log panes; empty for the single-pane layout.
+    /// The focused pane always shows the selected service.
+    pub panes: Vec<String>,
+    pub focused_pane: usize,
+    state_path: PathBuf,
+    /// The layout changed since `state.json` was last written.
+    state_dirty: bool,
+    pub keymap: Keymap,
+    runner: Runner,
+    /// Feeds results of background work, such as port checks, back into
+    /// the event loop.
+    events: mpsc::UnboundedSender<Event>,
+    /// Services to start again as soon as their current run has stopped.
+    pending_restart: HashSet<String>,
+    /// Enforces the services' limits when they run in this process.
+    watchdog: limits::Watchdog,
+    pub should_quit: bool,
+    /// Widths of the sidebar and resources columns, in percent.
+    pub columns: [u16; 2],
+    pub screen: ScreenLayout,
+    pub selection: Option<Selection>,
+    drag: Option<Drag>,
+    last_click: Option<(usize, Instant)>,
+    root_dir: PathBuf,
+    config_path: PathBuf,
+    recent_services: Vec<String>,
+    recent_commands: Vec<String>,
+    /// Keys of collapsed sidebar groups.
+    pub collapsed: HashSet<String>,
+    /// Sidebar group under the cursor; `None` when a service is selected.
+    pub selected_group: Option<String>,
+    pub sort: SortMode,
+    /// Ticks since startup, used to blink alerting services.
+    pub ticks: u64,
+    /// File to open in the user's editor; `run_app` suspends the TUI for it.
+    pub edit_file: Option<PathBuf>,
+    /// Kept alive for as long as the app runs; see `start_watching`.
+    watchers: HashMap<String, ServiceWatcher>,
+    /// Services restarted when their watched files change.
+    pub watching: HashSet<String>,
+    project_watcher: Option<ProjectWatcher>,
 }
 
 impl App {
-    pub fn new(services: Vec<Service>) -> Self {
-        Self {
+    pub fn new(
+        services: Vec<Service>,
+        config: &AppConfig,
+        config_path: PathBuf,
+        root_dir: PathBuf,
+        runner: Runner,
+        events: mpsc::UnboundedSender<Event>,
+    ) -> Self {
+        let log_dir = config.logs.resolve_dir(&root_dir);
+        let state_path = state::state_path(&root_dir);
+        let saved = UiState::load(&state_path);
+        let (keymap, message) = match Keymap::with_overrides(&config.keybindings) {
+            Ok(keymap) => (keymap, None),
+            Err(e) => (Keymap::default(), Some(format!("Invalid keybindings, using defaults: {}", e))),
+        };
+        // A daemon persists the logs of the services it runs itself.
+        let archive = (config.logs.persist && !runner.is_daemon()).then(|| LogArchive::new(log_dir.clone(), &config.logs));
+        let message = match (message, runner.is_daemon()) {
+            (None, true) => Some("Attached to daemon; quitting leaves services running".to_string()),
+            (None, false) if !config.warnings.is_empty() => Some(config.warnings.join("; ")),
+            (message, _) => message,
+        };
+        let mut app = Self {
             services,
             selected_index: 0,
             title: "DevRunner".to_string(),
             cpu_history: vec![0; 40],
+            log_views: HashMap::new(),
+            input_mode: InputMode::Normal,
+            input: String::new(),
+            search: String::new(),
+            message,
+            log_dir,
+            archive,
+            archive_view: None,
+            popup: None,
+            show_all: false,
+            merged_logs: LogBuffer::new(config.logs.max_lines, config.logs.max_bytes),
+            muted: HashSet::new(),
+            panes: Vec::new(),
+            focused_pane: 0,
+            state_path,
+            state_dirty: false,
+            keymap,
+            runner,
+            events,
+            pending_restart: HashSet::new(),
+            watchdog: limits::Watchdog::default(),
+            should_quit: false,
+            columns: [25, 25],
+            screen: ScreenLayout::default(),
+            selection: None,
+            drag: None,
+            last_click: None,
+            root_dir,
+            config_path,
+            recent_services: Vec::new(),
+            recent_commands: Vec::new(),
+            collapsed: HashSet::new(),
+            selected_group: None,
+            sort: SortMode::Tree,
+            ticks: 0,
+            edit_file: None,
+            watchers: HashMap::new(),
+            watching: HashSet::new(),
+            project_watcher: None,
+        };
+        app.restore_state(saved);
+        app
+    }
+
+    fn restore_state(&mut self, saved: UiState) {
+        if let Some(columns) = saved.columns {
+            self.set_columns(columns);
+        }
+        self.recent_services = saved.recent_services;
+        self.recent_commands = saved.recent_commands;
+        self.collapsed = saved.collapsed.into_iter().collect();
+        let panes: Vec<String> = saved
+            .panes
+            .into_iter()
+            .filter(|name| self.services.iter().any(|s| &s.name == name))
+            .take(MAX_PANES)
+            .collect();
+        if panes.len() < 2 {
+            return;
+        }
+        self.focused_pane = saved.focused_pane.min(panes.len() - 1);
+        self.panes = panes;
+        self.focus_pane(self.focused_pane);
+    }
+
+    /// Note that the layout changed; it is written out on the next tick, so
+    /// holding `j` or dragging a divider doesn't write a file per step.
+    fn state_changed(&mut self) {
+        self.state_dirty = true;
+    }
+
+    /// Write `state.json` if the layout changed since it was last written.
+    pub fn flush_state(&mut self) {
+        if !std::mem::take(&mut self.state_dirty) {
+            return;
+        }
+        let state = UiState {
+            panes: self.panes.clone(),
+            focused_pane: self.focused_pane,
+            columns: Some(self.columns),
+            recent_services: self.recent_services.clone(),
+            recent_commands: self.recent_commands.clone(),
+            collapsed: self.collapsed.iter().cloned().collect(),
+        };
+        if let Err(e) = state.save(&self.state_path) {
+            self.message = Some(format!("Failed to save UI state: {}", e));
+        }
+    }
+
+    /// Pin the selected service to a new log pane and focus it.
+    pub fn split_pane(&mut self) {
+        let Some(service) = self.services.get(self.selected_index) else {
+            return;
+        };
+        if self.panes.len() >= MAX_PANES {
+            self.message = Some(format!("At most {} log panes are supported", MAX_PANES));
+            return;
+        }
+        if self.panes.is_empty() {
+            self.panes.push(service.name.clone());
+        }
+        self.panes.push(service.name.clone());
+        self.focused_pane = self.panes.len() - 1;
+        self.state_changed();
+    }
+
+    pub fn close_pane(&mut self) {
+        if self.panes.is_empty() {
+            return;
+        }
+        self.panes.remove(self.focused_pane);
+        if self.panes.len() < 2 {
+            self.panes.clear();
+            self.focused_pane = 0;
+        } else {
+            self.focus_pane(self.focused_pane.min(self.panes.len() - 1));
+        }
+        self.state_changed();
+    }
+
+    pub fn cycle_pane_focus(&mut self) {
+        if !self.panes.is_empty() {
+            self.focus_pane((self.focused_pane + 1) % self.panes.len());
+            self.state_changed();
+        }
+    }
+
+    fn focus_pane(&mut self, index: usize) {
+        self.focused_pane = index;
+        self.selected_group = None;
+        if let Some(i) = self.services.iter().position(|s| s.name == self.panes[index]) {
+            self.selected_index = i;
+        }
+    }
+
+    /// Re-pin the focused pane to the newly selected service.
+    fn sync_focused_pane(&mut self) {
+        if let (Some(pane), Some(service)) = (self.panes.get_mut(self.focused_pane), self.services.get(self.selected_index)) {
+            *pane = service.name.clone();
+            self.state_changed();
+        }
+    }
+
+    /// Scroll state for the log pane's current source, created on first use.
+    pub fn selected_log_view(&mut self) -> Option<&mut LogView> {
+        let (key, _) = active_source(&self.services, self.selected_index, &self.archive_view, self.show_all.then_some(&self.merged_logs))?;
+        Some(self.log_views.entry(key).or_default())
+    }
+
+    /// Move the search cursor to the next (or previous) displayed line
+    /// matching the current search, wrapping around the buffer.
+    pub fn search_next(&mut self, forward: bool) {
+        if self.search.is_empty() {
+            return;
+        }
+        let Some((key, logs)) = active_source(&self.services, self.selected_index, &self.archive_view, self.show_all.then_some(&self.merged_logs)) else {
+            return;
+        };
+        let muted = self.show_all.then_some(&self.muted);
+        let view = self.log_views.entry(key).or_default();
+        let hits: Vec<u64> = logs
+            .iter()
+            .filter(|(_, line)| !muted.is_some_and(|m| m.contains(&line.service)))
+            .filter(|(_, line)| view.filter.matches(line) && !search::find_matches(&line.display_text(), &self.search).is_empty())
+            .map(|(seq, _)| seq)
+            .collect();
+        let pos = view.cursor.unwrap_or(logs.end_seq());
+        let target = if forward {
+            hits.iter().find(|&&i| i > pos).or(hits.first())
+        } else {
+            hits.iter().rev().find(|&&i| i < pos).or(hits.last())
+        };
+        match target {
+            Some(&line) => {
+                view.jump(line);
+                self.message = None;
+            }
+            None => self.message = Some(format!("Pattern not found: {}", self.search)),
+        }
+    }
+
+    pub fn begin_input(&mut self, mode: InputMode) {
+        self.input = match mode {
+            InputMode::Search => self.search.clone(),
+            InputMode::Filter => self.selected_log_view().map(|v| v.filter.expression.clone()).unwrap_or_default(),
+            InputMode::Normal => String::new(),
+        };
+        self.input_mode = mode;
+        self.message = None;
+    }
+
+    pub fn cycle_stream_filter(&mut self) {
+        if let Some(view) = self.selected_log_view() {
+            let stream = view.filter.stream.cycle();
+            if let Ok(filter) = LogFilter::parse(&view.filter.expression, stream) {
+                view.filter = filter;
+                view.end();
+            }
+        }
+    }
+
+    fn handle_input_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                if self.input_mode == InputMode::Search {
+                    self.search.clear();
+                    if let Some(view) = self.selected_log_view() {
+                        view.cursor = None;
+                    }
+                }
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Enter => {
+                if self.input_mode == InputMode::Filter {
+                    self.apply_filter();
+                }
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.on_input_changed();
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                self.on_input_changed();
+            }
+            _ => {}
+        }
+    }
+
+    fn on_input_changed(&mut self) {
+        if self.input_mode == InputMode::Search {
+            self.search = self.input.clone();
+            if let Some(view) = self.selected_log_view() {
+                view.cursor = None;
+            }
+            self.search_next(false);
+        }
+    }
+
+    fn apply_filter(&mut self) {
+        let expression = self.input.clone();
+        let Some(view) = self.selected_log_view() else {
+            return;
+        };
+        match LogFilter::parse(&expression, view.filter.stream) {
+            Ok(filter) => {
+                view.filter = filter;
+                view.cursor = None;
+                view.end();
+            }
+            Err(e) => self.message = Some(format!("Invalid filter: {}", e)),
+        }
+    }
+
+    /// Run a key-bound action against the current selection.
+    pub fn perform(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.should_quit = true,
+            Action::FindService => {
+                let palette = Palette::services(&self.services, &self.root_dir, &self.recent_services);
+                self.popup = Some(Popup::Palette(palette));
+            }
+            Action::CommandPalette => self.popup = Some(Popup::Palette(Palette::commands(&self.recent_commands))),
+            Action::Help => self.popup = Some(Popup::Help { scroll: 0 }),
+            Action::Cancel => {
+                if self.selection.take().is_some() {
+                    return;
+                }
+                if self.archive_view.take().is_none() {
+                    self.search.clear();
+                }
+                self.message = None;
+            }
+            Action::Next => self.next(),
+            Action::Previous => self.previous(),
+            Action::Start => self.targets().into_iter().for_each(|i| self.start_service(i)),
+            Action::Stop => self.targets().into_iter().for_each(|i| self.stop_service(i)),
+            Action::Restart => self.targets().into_iter().for_each(|i| self.restart_service(i)),
+            Action::Expand => self.expand(),
+            Action::Collapse => self.collapse(),
+            Action::SortByUsage => {
+                self.sort = self.sort.cycle();
+                self.selected_group = None;
+            }
+            Action::ScrollUp => self.with_log_view(|v| v.scroll_up(1)),
+            Action::ScrollDown => self.with_log_view(|v| v.scroll_down(1)),
+            Action::PageUp => self.with_log_view(LogView::page_up),
+            Action::PageDown => self.with_log_view(LogView::page_down),
+            Action::ScrollTop => self.with_log_view(LogView::home),
+            Action::ScrollBottom => self.with_log_view(LogView::end),
+            Action::Search => self.begin_input(InputMode::Search),
+            Action::SearchNext => self.search_next(true),
+            Action::SearchPrev => self.search_next(false),
+            Action::Filter => self.begin_input(InputMode::Filter),
+            Action::CycleStream => self.cycle_stream_filter(),
+            Action::ExpandLine => self.open_line_detail(),
+            Action::PastRuns => self.open_runs(),
+            Action::ToggleAll => self.toggle_all_services(),
+            Action::ToggleMute => self.toggle_mute(),
+            Action::ToggleWatch => self.toggle_watch(),
+            Action::SplitPane => self.split_pane(),
+            Action::ClosePane => self.close_pane(),
+            Action::FocusNextPane => self.cycle_pane_focus(),
+        }
+    }
+
+    fn with_log_view(&mut self, f: impl FnOnce(&mut LogView)) {
+        if let Some(view) = self.selected_log_view() {
+            f(view);
+        }
+    }
+
+    /// Start a stopped service, first checking that its configured port is
+    /// free. The check walks `/proc`, so it runs off the UI task and a taken
+    /// port comes back as `Event::PortTaken`.
+    pub fn start_service(&mut self, index: usize) {
+        let Some(service) = self.services.get(index) else {
+            return;
+        };
+        if matches!(service.status, ServiceStatus::Running(_)) {
+            return;
+        }
+        let Some(port) = service.port else {
+            self.spawn_service(index);
+            return;
+        };
+        let service = service.clone();
+        let runner = self.runner.clone();
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let holder = tokio::task::spawn_blocking(move || ports::tcp_port_holder(port)).await.ok().flatten();
+            match holder {
+                Some(holder) => {
+                    let _ = events.send(Event::PortTaken(service.name, port, holder));
+                }
+                None => runner.start(service).await,
+            }
+        });
+    }
+
+    /// A service's port turned out to be taken: open a popup offering to
+    /// kill the holder, or log it when another popup is already open.
+    fn port_taken(&mut self, name: String, port: u16, holder: Holder) {
+        let Some(index) = self.services.iter().position(|s| s.name == name) else {
+            return;
+        };
+        let owner = self
+            .services
+            .iter()
+            .find(|s| s.metrics.ports.iter().any(|l| l.protocol == ports::Protocol::Tcp && l.port == port))
+            .map(|s| s.name.clone());
+        if self.popup.is_none() {
+            self.popup = Some(Popup::PortConflict { service: index, port, holder, owner });
+        } else {
+            let text = format!("Not started: port {} is in use by {}", port, describe_holder(&holder, owner.as_deref()));
+            self.push_log(LogLine::system(&name, LogLevel::Warn, text));
+        }
+    }
+
+    fn spawn_service(&mut self, index: usize) {
+        let service = self.services[index].clone();
+        let runner = self.runner.clone();
+        tokio::spawn(async move { runner.start(service).await });
+    }
+
+    /// Stop whatever holds `port`, then start the service once it is free.
+    fn kill_holder_and_start(&mut self, index: usize, port: u16, holder: Holder) {
+        let service = self.services[index].clone();
+        self.push_log(LogLine::system(
+            &service.name,
+            LogLevel::Warn,
+            format!("Stopping {} (pid {}) to free port {}", holder.name, holder.pid, port),
+        ));
+        let runner = self.runner.clone();
+        tokio::spawn(async move {
+            // Start regardless; if the port is still taken the service's own
+            // error says so.
+            ports::kill_holder(holder.pid, port).await;
+            runner.start(service).await;
+        });
+    }
+
+    pub fn stop_service(&mut self, index: usize) {
+        let Some(service) = self.services.get(index) else {
+            return;
+        };
+        self.pending_restart.remove(&service.name);
+        if matches!(service.status, ServiceStatus::Running(_)) && !self.runner.stop(&service.name) {
+            self.message = Some(format!("{} is still starting; try again", service.name));
+        }
+    }
+
+    /// Stop the service if it is running and start it again once it has exited.
+    pub fn restart_service(&mut self, index: usize) {
+        let Some(service) = self.services.get(index) else {
+            return;
+        };
+        if matches!(service.status, ServiceStatus::Running(_)) {
+            if self.runner.restart(&service.name) {
+                self.pending_restart.insert(service.name.clone());
+            }
+        } else {
+            self.start_service(index);
+        }
+    }
+
+    /// Carry out a request from the HTTP API.
+    fn control(&mut self, control: Control) {
+        let Some(index) = self.services.iter().position(|s| s.name == control.service()) else {
+            return;
+        };
+        match control {
+            Control::Start(_) => self.start_service(index),
+            Control::Stop(_) => self.stop_service(index),
+            Control::Restart(_) => self.restart_service(index),
+        }
+    }
+
+    /// Set the side column widths, keeping every column usable.
+    fn set_columns(&mut self, [sidebar, resources]: [u16; 2]) {
+        let sidebar = sidebar.clamp(MIN_COLUMN, 100 - MIN_LOG_COLUMN - MIN_COLUMN);
+        let resources = resources.clamp(MIN_COLUMN, 100 - MIN_LOG_COLUMN - sidebar);
+        self.columns = [sidebar, resources];
+    }
+
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if self.popup.is_some() {
+            return;
+        }
+        let (x, y) = (mouse.column, mouse.row);
+        let position = Position::new(x, y);
+        let pane_at = self.screen.panes.iter().position(|(_, area)| area.contains(position));
+        match mouse.kind {
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let up = mouse.kind == MouseEventKind::ScrollUp;
+                if self.screen.sidebar.contains(position) {
+                    if up { self.previous() } else { self.next() }
+                    return;
+                }
+                self.selection = None;
+                let view = match pane_at {
+                    Some(pane) => {
+                        let key = self.screen.panes[pane].0.clone();
+                        Some(self.log_views.entry(key).or_default())
+                    }
+                    None => self.selected_log_view(),
+                };
+                if let Some(view) = view {
+                    if up { view.scroll_up(WHEEL_STEP) } else { view.scroll_down(WHEEL_STEP) }
+                }
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.selection = None;
+                let sidebar = self.screen.sidebar;
+                let resources = self.screen.resources;
+                if y >= sidebar.y && y < sidebar.bottom() && (x + 1 == sidebar.right() || x == sidebar.right()) {
+                    self.drag = Some(Drag::Divider(0));
+                } else if y >= resources.y && y < resources.bottom() && (x + 1 == resources.x || x == resources.x) {
+                    self.drag = Some(Drag::Divider(1));
+                } else if sidebar.contains(position) {
+                    self.click_sidebar(y);
+                } else if let Some(pane) = pane_at {
+                    if !self.panes.is_empty() && pane != self.focused_pane {
+                        self.focus_pane(pane);
+                        self.state_changed();
+                    }
+                    let area = self.screen.panes[pane].1;
+                    let cell = (x - area.x, y - area.y);
+                    self.selection = Some(Selection { pane, start: cell, end: cell });
+                    self.drag = Some(Drag::Select);
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => match self.drag {
+                Some(Drag::Divider(side)) => {
+                    let main = self.screen.main;
+                    if main.width == 0 {
+                        return;
+                    }
+                    let offset = if side == 0 { x.saturating_sub(main.x) + 1 } else { main.right().saturating_sub(x) };
+                    let percent = (offset as u32 * 100 / main.width as u32) as u16;
+                    let mut columns = self.columns;
+                    columns[side] = percent;
+                    self.set_columns(columns);
+                }
+                Some(Drag::Select) => {
+                    if let Some(selection) = &mut self.selection
+                        && let Some((_, area)) = self.screen.panes.get(selection.pane)
+                    {
+                        let col = x.clamp(area.x, area.right().saturating_sub(1)) - area.x;
+                        let row = y.clamp(area.y, area.bottom().saturating_sub(1)) - area.y;
+                        selection.end = (col, row);
+                    }
+                }
+                None => {}
+            },
+            MouseEventKind::Up(MouseButton::Left) => match self.drag.take() {
+                Some(Drag::Divider(_)) => self.state_changed(),
+                Some(Drag::Select) => self.copy_selection(),
+                None => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Select the clicked row; a second click starts or stops a service,
+    /// or expands or collapses a group.
+    fn click_sidebar(&mut self, y: u16) {
+        let row = y.saturating_sub(self.screen.sidebar.y + 1) as usize + self.screen.sidebar_offset;
+        let rows = self.sidebar_rows();
+        let Some(clicked) = rows.get(row) else {
+            return;
+        };
+        let double = self
+            .last_click
+            .is_some_and(|(last, at)| last == row && at.elapsed() < DOUBLE_CLICK);
+        self.select_row(clicked);
+        if !double {
+            self.last_click = Some((row, Instant::now()));
+            return;
+        }
+        self.last_click = None;
+        match clicked {
+            SidebarRow::Group { key, .. } => self.toggle_group(key),
+            SidebarRow::Service { index, .. } => {
+                if matches!(self.services[*index].status, ServiceStatus::Running(_)) {
+                    self.stop_service(*index);
+                } else {
+                    self.start_service(*index);
+                }
+            }
+        }
+    }
+
+    fn copy_selection(&mut self) {
+        let Some(selection) = self.selection else {
+            return;
+        };
+        if selection.start == selection.end {
+            self.selection = None;
+            return;
+        }
+        let Some((key, _)) = self.screen.panes.get(selection.pane) else {
+            return;
+        };
+        let text = self
+            .log_views
+            .get(key)
+            .map(|view| selection.text(&view.visible_rows))
+            .unwrap_or_default();
+        self.message = Some(match clipboard::copy(&text) {
+            Ok(()) => format!("Copied {} characters", text.chars().count()),
+            Err(e) => format!("Failed to copy selection: {}", e),
+        });
+    }
+
+    /// Jump to a service picked in the finder or run a palette command,
+    /// remembering it for the recently used ordering.
+    fn run_target(&mut self, target: Target) {
+        match target {
+            Target::Service(index) => {
+                if let Some(service) = self.services.get(index) {
+                    palette::remember(&mut self.recent_services, service.name.clone());
+                    self.select_service(index);
+                }
+            }
+            Target::Command(command) => {
+                palette::remember(&mut self.recent_commands, command.id());
+                self.run_command(command);
+            }
+        }
+        self.state_changed();
+    }
+
+    fn run_command(&mut self, command: Command) {
+        match command {
+            Command::Run(action) => self.perform(action),
+            Command::StartGroup | Command::StopGroup => {
+                let Some(path) = self.services.get(self.selected_index).map(|s| s.path.clone()) else {
+                    return;
+                };
+                let group: Vec<usize> = if self.selected_group.is_some() {
+                    self.targets()
+                } else {
+                    (0..self.services.len()).filter(|&i| self.services[i].path == path).collect()
+                };
+                for index in group {
+                    if command == Command::StartGroup {
+                        self.start_service(index);
+                    } else {
+                        self.stop_service(index);
+                    }
+                }
+            }
+            Command::StartAll => (0..self.services.len()).for_each(|i| self.start_service(i)),
+            Command::StopAll => (0..self.services.len()).for_each(|i| self.stop_service(i)),
+            Command::RestartFailed => {
+                let failed: Vec<usize> = (0..self.services.len())
+                    .filter(|&i| self.services[i].status == ServiceStatus::Failed)
+                    .collect();
+                self.message = Some(format!("Restarting {} failed services", failed.len()));
+                for index in failed {
+                    self.start_service(index);
+                }
+            }
+            Command::ClearLogs => {
+                if let Some(service) = self.services.get_mut(self.selected_index) {
+                    service.logs.clear();
+                    self.log_views.remove(&service.name);
+                }
+            }
+            Command::OpenConfig => self.edit_file = Some(self.config_path.clone()),
+        }
+    }
+
+    /// Open the list of persisted runs for the selected service.
+    pub fn open_runs(&mut self) {
+        let Some(service) = self.services.get(self.selected_index) else {
+            return;
+        };
+        match archive::list_runs(&self.log_dir, &service.name) {
+            Ok(runs) if runs.is_empty() => {
+                self.message = Some(format!("No persisted logs for {} in {}", service.name, self.log_dir.display()));
+            }
+            Ok(runs) => {
+                self.popup = Some(Popup::Runs {
+                    service: service.name.clone(),
+                    runs,
+                    selected: 0,
+                });
+            }
+            Err(e) => self.message = Some(format!("Failed to list runs: {}", e)),
+        }
+    }
+
+    fn handle_popup_key(&mut self, key: KeyEvent) {
+        let Some(popup) = &mut self.popup else {
+            return;
+        };
+        match popup {
+            Popup::Runs { service, runs, selected } => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => self.popup = None,
+                KeyCode::Down | KeyCode::Char('j') => *selected = (*selected + 1) % runs.len(),
+                KeyCode::Up | KeyCode::Char('k') => *selected = (*selected + runs.len() - 1) % runs.len(),
+                KeyCode::Enter => {
+                    let run = runs[*selected].clone();
+                    let service = service.clone();
+                    self.popup = None;
+                    self.load_archived_run(service, run);
+                }
+                _ => {}
+            },
+            Popup::Palette(palette) => match key.code {
+                KeyCode::Esc => self.popup = None,
+                KeyCode::Enter => {
+                    let target = palette.target();
+                    self.popup = None;
+                    if let Some(target) = target {
+                        self.run_target(target);
+                    }
+                }
+                KeyCode::Down | KeyCode::Tab => palette.select_next(),
+                KeyCode::Up | KeyCode::BackTab => palette.select_previous(),
+                KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => palette.select_next(),
+                KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => palette.select_previous(),
+                KeyCode::Backspace => {
+                    palette.query.pop();
+                    palette.update();
+                }
+                KeyCode::Char(c) => {
+                    palette.query.push(c);
+                    palette.update();
+                }
+                _ => {}
+            },
+            Popup::Help { scroll } => match key.code {
+                KeyCode::Down | KeyCode::Char('j') => *scroll += 1,
+                KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+                _ => self.popup = None,
+            },
+            Popup::Detail { lines, scroll, .. } => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('v') => self.popup = None,
+                KeyCode::Down | KeyCode::Char('j') => {
+                    *scroll = (*scroll + 1).min(lines.len().saturating_sub(1) as u16);
+                }
+                KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+                _ => {}
+            },
+            Popup::ConfigDiff { changes, scroll } => match key.code {
+                KeyCode::Down | KeyCode::Char('j') => *scroll += 1,
+                KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+                KeyCode::Char('r') => {
+                    let changes = std::mem::take(changes);
+                    self.popup = None;
+                    self.restart_changed(&changes);
+                }
+                _ => self.popup = None,
+            },
+            Popup::PortConflict { service, port, holder, .. } => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('n') => self.popup = None,
+                // An uninspectable holder can't be killed from here.
+                KeyCode::Char('k') if holder.pid != 0 => {
+                    let (service, port, holder) = (*service, *port, holder.clone());
+                    self.popup = None;
+                    self.kill_holder_and_start(service, port, holder);
+                }
+                KeyCode::Char('s') => {
+                    let service = *service;
+                    self.popup = None;
+                    self.spawn_service(service);
+                }
+                _ => {}
+            },
+        }
+    }
+
+    /// Expand the current search match, or the last visible line, into a
+    /// popup showing the pretty-printed record.
+    pub fn open_line_detail(&mut self) {
+        let Some((key, logs)) = active_source(&self.services, self.selected_index, &self.archive_view, self.show_all.then_some(&self.merged_logs)) else {
+            return;
+        };
+        let Some(view) = self.log_views.get(&key) else {
+            return;
+        };
+        let Some(line) = view.cursor.or(view.last_visible).and_then(|seq| logs.get(seq)) else {
+            return;
+        };
+        let level = line.level.map(|l| l.as_str()).unwrap_or("-");
+        self.popup = Some(Popup::Detail {
+            title: format!("{} {} {}", line.service, line.timestamp.format("%H:%M:%S%.3f"), level),
+            lines: logparse::pretty(&line.raw, line.record.as_ref()),
+            scroll: 0,
+        });
+    }
+
+    fn load_archived_run(&mut self, service: String, run: RunInfo) {
+        match archive::read_run(&run.path, &service) {
+            Ok(lines) => {
+                let mut logs = LogBuffer::new(usize::MAX, usize::MAX);
+                for line in lines {
+                    logs.push(line);
+                }
+                let key = format!("{}#{}", service, run.file_name);
+                self.log_views.remove(&key);
+                self.archive_view = Some(ArchiveView {
+                    service,
+                    file_name: run.file_name,
+                    logs,
+                });
+            }
+            Err(e) => self.message = Some(format!("Failed to read {}: {}", run.path.display(), e)),
+        }
+    }
+
+    /// Append a line to a service's buffer and, if enabled, its log file.
+    fn push_log(&mut self, line: LogLine) {
+        let Some(service) = self.services.iter_mut().find(|s| s.name == line.service) else {
+            return;
+        };
+        if let Some(store) = &mut self.archive
+            && let Err(e) = store.write(&line)
+        {
+            self.message = Some(format!("Log persistence disabled: {}", e));
+            self.archive = None;
+        }
+        let view = self.log_views.entry(line.service.clone()).or_default();
+        if view.filter.matches(&line) {
+            view.on_new_line();
+        }
+        if !self.muted.contains(&line.service) {
+            let view = self.log_views.entry(ALL_SERVICES_KEY.to_string()).or_default();
+            if view.filter.matches(&line) {
+                view.on_new_line();
+            }
+        }
+        self.merged_logs.push(line.clone());
+        service.logs.push(line);
+    }
+
+    pub fn toggle_all_services(&mut self) {
+        self.show_all = !self.show_all;
+    }
+
+    /// Hide or show the selected service in the merged view.
+    pub fn toggle_mute(&mut self) {
+        let Some(service) = self.services.get(self.selected_index) else {
+            return;
+        };
+        if !self.muted.remove(&service.name) {
+            self.muted.insert(service.name.clone());
+        }
+    }
+
+    /// Watch the files of every service with a `watch` section, sending
+    /// changes to `tx`. Watching starts enabled for each of them, except
+    /// where it was turned off before.
+    pub fn start_watching(&mut self, config: &AppConfig, tx: &mpsc::UnboundedSender<Event>) {
+        let turned_off: HashSet<String> = self.watchers.keys().filter(|n| !self.watching.contains(*n)).cloned().collect();
+        let mut previous = std::mem::take(&mut self.watchers);
+        self.watching.clear();
+        let mut errors = Vec::new();
+        for service in &self.services {
+            let Some(watch_config) = config.watch(&service.name) else {
+                continue;
+            };
+            // Keep the watcher of a service whose directory and settings
+            // haven't changed.
+            let watcher = match previous.remove(&service.name) {
+                Some(watcher) if watcher.is_for(service, watch_config) => Ok(watcher),
+                _ => watch::watch(service, watch_config, tx.clone()),
+            };
+            match watcher {
+                Ok(watcher) => {
+                    self.watchers.insert(service.name.clone(), watcher);
+                    if !turned_off.contains(&service.name) {
+                        self.watching.insert(service.name.clone());
+                    }
+                }
+                Err(e) => errors.push(format!("{}: {:#}", service.name, e)),
+            }
+        }
+        if !errors.is_empty() {
+            self.message = Some(format!("File watching failed for {}", errors.join("; ")));
+        }
+    }
+
+    /// Watch manifests and the config files for changes. A daemon's services
+    /// are fixed when it starts, so there is nothing to rescan when attached.
+    pub fn watch_project(&mut self, tx: &mpsc::UnboundedSender<Event>) {
+        if self.runner.is_daemon() {
+            return;
+        }
+        let config_files = config::candidate_paths(Some(&self.config_path), &self.root_dir);
+        match ProjectWatcher::new(&self.root_dir, config_files, tx.clone()) {
+            Ok(watcher) => self.project_watcher = Some(watcher),
+            Err(e) => self.message = Some(format!("Not watching for new services: {:#}", e)),
+        }
+    }
+
+    /// Reload the config if any of its files changed, then rescan the project
+    /// and merge the services found into the list. A config that fails to
+    /// load is reported and the previous one kept.
+    fn project_changed(&mut self, paths: Vec<PathBuf>, config: &mut AppConfig, tx: &mpsc::UnboundedSender<Event>) {
+        let before = reload::snapshot(&self.services, config);
+        let mut reloaded = false;
+        let config_files = config::candidate_paths(Some(&self.config_path), &self.root_dir);
+        if paths.iter().any(|p| config_files.contains(p)) {
+            match config::load_config(Some(self.config_path.clone()), &self.root_dir) {
+                Ok(new_config) => {
+                    *config = new_config;
+                    reloaded = true;
+                }
+                Err(e) => self.message = Some(format!("Config error, keeping the previous config: {:#}", e)),
+            }
+        }
+        match scanner::discover(&self.root_dir, config, &self.config_path) {
+            Ok(found) => self.merge_services(found, config),
+            Err(e) => self.message = Some(format!("Rescan failed: {:#}", e)),
+        }
+        self.start_watching(config, tx);
+        if let Some(watcher) = &mut self.project_watcher
+            && let Err(e) = watcher.refresh()
+        {
+            self.message = Some(format!("{:#}", e));
+        }
+
+        let changes = reload::diff(&before, &reload::snapshot(&self.services, config));
+        if reloaded && !changes.is_empty() && self.popup.is_none() {
+            self.popup = Some(Popup::ConfigDiff { changes, scroll: 0 });
+        } else if reloaded && changes.is_empty() {
+            // Editors often write a file in several steps; the first one
+            // already brought up the diff.
+            if !matches!(self.popup, Some(Popup::ConfigDiff { .. })) {
+                self.message = Some("Config reloaded; no services changed".to_string());
+            }
+        } else if !changes.is_empty() {
+            let count = |kind| changes.iter().filter(|c| c.kind == kind).count();
+            self.message = Some(format!(
+                "Services changed: {} added, {} removed, {} modified",
+                count(ChangeKind::Added),
+                count(ChangeKind::Removed),
+                count(ChangeKind::Modified)
+            ));
+        }
+        if reloaded && !config.warnings.is_empty() {
+            self.message = Some(config.warnings.join("; "));
+        }
+        if reloaded {
+            match Keymap::with_overrides(&config.keybindings) {
+                Ok(keymap) => self.keymap = keymap,
+                Err(e) => self.message = Some(format!("Invalid keybindings, keeping the previous ones: {}", e)),
+            }
+        }
+    }
+
+    /// Fold a fresh scan into the service list. Services found again keep
+    /// their status, logs and position; new ones are added at the end.
+    /// Services no longer found are dropped, or marked stale while running.
+    fn merge_services(&mut self, found: Vec<Service>, config: &AppConfig) {
+        let names: HashSet<String> = found.iter().map(|s| s.name.clone()).collect();
+        for service in found {
+            match self.services.iter_mut().find(|s| s.name == service.name) {
+                Some(existing) => {
+                    existing.path = service.path;
+                    existing.manifest = service.manifest;
+                    existing.project_type = service.project_type;
+                    existing.command = service.command;
+                    existing.stale = false;
+                    config.apply(existing);
+                }
+                None => self.services.push(service),
+            }
+        }
+        for index in (0..self.services.len()).rev() {
+            let service = &mut self.services[index];
+            if names.contains(&service.name) {
+                continue;
+            }
+            if matches!(service.status, ServiceStatus::Running(_)) {
+                service.stale = true;
+            } else {
+                self.remove_service(index);
+            }
+        }
+    }
+
+    /// Restart the running services whose command or environment changed in
+    /// the reload being shown.
+    fn restart_changed(&mut self, changes: &[ServiceChange]) {
+        for change in changes.iter().filter(|c| c.needs_restart) {
+            if let Some(index) = self.services.iter().position(|s| s.name == change.service)
+                && matches!(self.services[index].status, ServiceStatus::Running(_))
+            {
+                self.restart_service(index);
+            }
+        }
+    }
+
+    /// Drop a service from the list, along with everything that refers to it.
+    fn remove_service(&mut self, index: usize) {
+        let name = self.services.remove(index).name;
+        self.pending_restart.remove(&name);
+        self.watchdog.forget(&name);
+        self.watchers.remove(&name);
+        self.watching.remove(&name);
+        self.muted.remove(&name);
+        self.log_views.remove(&name);
+        if self.archive_view.as_ref().is_some_and(|v| v.service == name) {
+            self.archive_view = None;
+        }
+        match &mut self.popup {
+            Some(Popup::PortConflict { service, .. }) if *service == index => self.popup = None,
+            Some(Popup::PortConflict { service, .. }) if *service > index => *service -= 1,
+            Some(Popup::Runs { service, .. }) if *service == name => self.popup = None,
+            // Holds service indices.
+            Some(Popup::Palette(_)) => self.popup = None,
+            _ => {}
+        }
+        if self.selected_index > index || self.selected_index >= self.services.len() {
+            self.selected_index = self.selected_index.saturating_sub(1);
+        }
+        if let Some(pane) = self.panes.iter().position(|p| *p == name) {
+            self.panes.remove(pane);
+            if self.panes.len() < 2 {
+                self.panes.clear();
+                self.focused_pane = 0;
+            } else {
+                self.focus_pane(self.focused_pane.min(self.panes.len() - 1));
+            }
+            self.state_changed();
+        }
+    }
+
+    pub fn toggle_watch(&mut self) {
+        let Some(service) = self.services.get(self.selected_index) else {
+            return;
+        };
+        let name = service.name.clone();
+        self.message = Some(if self.watching.remove(&name) {
+            format!("Stopped watching {}", name)
+        } else if !self.watchers.contains_key(&name) {
+            format!("No watch paths configured for {}", name)
+        } else {
+            self.watching.insert(name.clone());
+            format!("Watching {} for changes", name)
+        });
+    }
+
+    /// Restart a watched service whose files changed, or start it again if
+    /// its last run ended. Services stopped by hand stay stopped.
+    fn files_changed(&mut self, name: String, paths: Vec<PathBuf>) {
+        if !self.watching.contains(&name) {
+            return;
+        }
+        let Some(index) = self.services.iter().position(|s| s.name == name) else {
+            return;
+        };
+        if self.services[index].status == ServiceStatus::Stopped {
+            return;
+        }
+        let Some(first) = paths.first() else {
+            return;
+        };
+        let mut text = format!("Restarting due to change in {}", first.display());
+        if paths.len() > 1 {
+            text.push_str(&format!(" (+{} more)", paths.len() - 1));
+        }
+        self.push_log(LogLine::system(&name, LogLevel::Info, text));
+        self.restart_service(index);
+    }
+
+    fn set_status(&mut self, name: String, status: ServiceStatus) {
+        let Some(service) = self.services.iter_mut().find(|s| s.name == name) else {
+            return;
+        };
+        let starting = matches!(status, ServiceStatus::Running(_)) && !matches!(service.status, ServiceStatus::Running(_));
+        if starting && let Some(store) = &mut self.archive {
+            store.begin_run(&name);
+        }
+        self.watchdog.status_changed(&name, &status);
+        let exited = !matches!(status, ServiceStatus::Running(_));
+        service.status = status;
+        if exited && service.stale {
+            if let Some(index) = self.services.iter().position(|s| s.name == name) {
+                self.remove_service(index);
+            }
+            return;
+        }
+        if exited
+            && self.pending_restart.remove(&name)
+            && let Some(index) = self.services.iter().position(|s| s.name == name)
+        {
+            self.start_service(index);
         }
     }
 
     pub fn next(&mut self) {
-        if !self.services.is_empty() {
-            self.selected_index = (self.selected_index + 1) % self.services.len();
+        let rows = self.sidebar_rows();
+        if !rows.is_empty() {
+            let row = self.cursor_row(&rows).map_or(0, |r| (r + 1) % rows.len());
+            self.select_row(&rows[row]);
         }
     }
 
     pub fn previous(&mut self) {
-        if !self.services.is_empty() {
-            if self.selected_index > 0 {
-                self.selected_index -= 1;
-            } else {
-                self.selected_index = self.services.len() - 1;
+        let rows = self.sidebar_rows();
+        if !rows.is_empty() {
+            let row = self.cursor_row(&rows).map_or(0, |r| (r + rows.len() - 1) % rows.len());
+            self.select_row(&rows[row]);
+        }
+    }
+
+    /// The sidebar tree as currently expanded, or a flat list of services
+    /// by descending usage while sorting.
+    pub fn sidebar_rows(&self) -> Vec<SidebarRow> {
+        let key = |i: &usize| {
+            let usage = self.services[*i].metrics.current.unwrap_or_default();
+            match self.sort {
+                SortMode::Cpu => (usage.cpu * 100.0) as u64,
+                _ => usage.rss,
+            }
+        };
+        if self.sort == SortMode::Tree {
+            return tree::visible_rows(&self.services, &self.root_dir, &self.collapsed);
+        }
+        let mut order: Vec<usize> = (0..self.services.len()).collect();
+        order.sort_by_key(|i| std::cmp::Reverse(key(i)));
+        order
+            .into_iter()
+            .map(|index| SidebarRow::Service { index, label: self.services[index].name.clone(), depth: 0 })
+            .collect()
+    }
+
+    /// Row of the sidebar cursor: the selected group, or else the selected service.
+    pub fn cursor_row(&self, rows: &[SidebarRow]) -> Option<usize> {
+        rows.iter().position(|row| match (row, &self.selected_group) {
+            (SidebarRow::Group { key, .. }, Some(selected)) => key == selected,
+            (SidebarRow::Service { index, .. }, None) => *index == self.selected_index,
+            _ => false,
+        })
+    }
+
+    fn select_row(&mut self, row: &SidebarRow) {
+        match row {
+            SidebarRow::Group { key, .. } => self.selected_group = Some(key.clone()),
+            SidebarRow::Service { index, .. } => {
+                self.selected_group = None;
+                if *index != self.selected_index {
+                    self.selected_index = *index;
+                    self.sync_focused_pane();
+                }
             }
         }
     }
+
+    /// Select a service, expanding any collapsed groups hiding it.
+    pub fn select_service(&mut self, index: usize) {
+        for key in tree::ancestors_of(&self.services, &self.root_dir, index) {
+            self.collapsed.remove(&key);
+        }
+        self.select_row(&SidebarRow::Service { index, label: String::new(), depth: 0 });
+    }
+
+    /// Services acted on by start/stop/restart: the whole subtree when a
+    /// group is selected, otherwise the selected service.
+    fn targets(&self) -> Vec<usize> {
+        let rows = self.sidebar_rows();
+        match self.cursor_row(&rows).map(|r| &rows[r]) {
+            Some(SidebarRow::Group { members, .. }) => members.clone(),
+            _ if self.selected_index < self.services.len() => vec![self.selected_index],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Expand the selected group, or step into it if already expanded.
+    fn expand(&mut self) {
+        let Some(key) = self.selected_group.clone() else {
+            return;
+        };
+        if !self.collapsed.remove(&key) {
+            self.next();
+        }
+        self.state_changed();
+    }
+
+    /// Collapse the selected group, or move to the group containing the
+    /// selected row.
+    fn collapse(&mut self) {
+        let rows = self.sidebar_rows();
+        let Some(row) = self.cursor_row(&rows) else {
+            return;
+        };
+        if let SidebarRow::Group { key, collapsed: false, .. } = &rows[row] {
+            self.collapsed.insert(key.clone());
+            self.state_changed();
+            return;
+        }
+        let depth = rows[row].depth();
+        if let Some(parent) = rows[..row].iter().rev().find(|r| r.depth() < depth) {
+            self.select_row(parent);
+        }
+    }
+
+    fn toggle_group(&mut self, key: &str) {
+        if !self.collapsed.remove(key) {
+            self.collapsed.insert(key.to_string());
+        }
+        self.state_changed();
+    }
     
+    /// Sample every running service's process tree.
+    fn refresh_service_metrics(&mut self, sys: &mut System) {
+        let samples = metrics::sample(sys, &metrics::running(&self.services));
+        metrics::record(&mut self.services, samples);
+    }
+
+    /// Apply the services' limits to the latest usage. A daemon does that
+    /// itself, so attached to one this only updates which services blink.
+    fn check_limits(&mut self) {
+        if self.runner.is_daemon() {
+            for service in &mut self.services {
+                service.metrics.alerting =
+                    service.metrics.current.is_some_and(|usage| !limits::alerts(&service.limits, &usage).is_empty());
+            }
+            return;
+        }
+        let (lines, restarts) = self.watchdog.check(&mut self.services);
+        for line in lines {
+            self.push_log(line);
+        }
+        for index in restarts {
+            self.restart_service(index);
+        }
+    }
+
     pub fn on_tick(&mut self, sys: &mut System) {
         sys.refresh_cpu_usage();
         sys.refresh_memory();
+        self.refresh_service_metrics(sys);
+        self.check_limits();
+        self.flush_state();
+        self.ticks += 1;
         let usage = sys.global_cpu_usage() as u64;
         
         self.cpu_history.push(usage);
         if self.cpu_history.len() > 40 {
             self.cpu_history.remove(0);
         }
+
+        if let Some(store) = &mut self.archive
+            && let Err(e) = store.flush()
+        {
+            self.message = Some(format!("Log persistence disabled: {}", e));
+            self.archive = None;
+        }
+    }
+}
+
+/// `node (pid 1234)`, naming the DevRunner service it belongs to if known.
+pub fn describe_holder(holder: &Holder, owner: Option<&str>) -> String {
+    let mut text = if holder.pid == 0 {
+        holder.name.clone()
+    } else {
+        format!("{} (pid {})", holder.name, holder.pid)
+    };
+    if let Some(owner) = owner {
+        text.push_str(&format!(", part of {}", owner));
+    }
+    text
+}
+
+/// Run `$VISUAL`, `$EDITOR` or `vi` on `path` and wait for it to exit.
+async fn open_in_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // The variable may carry arguments, e.g. `code --wait`.
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = tokio::process::Command::new(program).args(parts).arg(path).status().await?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", program, status);
     }
+    Ok(())
 }
 
-pub async fn run_app(services: Vec<Service>) -> Result<()> {
+pub async fn run_app(services: Vec<Service>, mut config: AppConfig, config_path: PathBuf, root_dir: PathBuf) -> Result<()> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -68,7 +1721,14 @@ pub async fn run_app(services: Vec<Service>) -> Result<()> {
     let mut terminal = Terminal::new(backend)?;
 
     let (tx, mut rx) = mpsc::unbounded_channel();
-    let process_manager = Arc::new(ProcessManager::new(tx.clone()));
+    // Services keep running in a daemon for this directory, if there is one.
+    #[cfg(unix)]
+    let runner = match daemon::Client::attach(&root_dir, tx.clone()).await {
+        Some(client) => Runner::Daemon(client),
+        None => Runner::Local(Arc::new(ProcessManager::new(tx.clone()))),
+    };
+    #[cfg(not(unix))]
+    let runner = Runner::Local(Arc::new(ProcessManager::new(tx.clone())));
 
     let mut sys = System::new_all();
     sys.refresh_all();
@@ -85,8 +1745,15 @@ pub async fn run_app(services: Vec<Service>) -> Result<()> {
     });
 
     let tx_input = tx.clone();
+    // Set while an external editor owns the terminal so keys reach it instead.
+    let input_paused = Arc::new(AtomicBool::new(false));
+    let paused = input_paused.clone();
     std::thread::spawn(move || {
         loop {
+            if paused.load(Ordering::Relaxed) {
+                std::thread::sleep(Duration::from_millis(50));
+                continue;
+            }
             if event::poll(Duration::from_millis(250)).expect("Poll failed") {
                 match event::read().expect("Read failed") {
                     CEvent::Key(key) => {
@@ -97,63 +1764,92 @@ pub async fn run_app(services: Vec<Service>) -> Result<()> {
                         }
                         if tx_input.send(Event::Key(key)).is_err() { break; }
                     }
-                    CEvent::Mouse(mouse) => {
-                         if tx_input.send(Event::Mouse(mouse)).is_err() { break; }
-                    }
+                    CEvent::Mouse(mouse) if tx_input.send(Event::Mouse(mouse)).is_err() => break,
                     _ => {}
                 }
             }
         }
     });
 
-    let mut app = App::new(services);
+    // An attached daemon serves the API itself.
+    let hub = if config.api.enabled && !runner.is_daemon() {
+        Some(api::serve(&config.api, &root_dir, &services, tx.clone()).await)
+    } else {
+        None
+    };
+    let mut app = App::new(services, &config, config_path, root_dir, runner.clone(), tx.clone());
+    let hub = match hub {
+        Some(Ok(hub)) => Some(hub),
+        Some(Err(e)) => {
+            app.message = Some(format!("HTTP API disabled: {:#}", e));
+            None
+        }
+        None => None,
+    };
+    app.start_watching(&config, &tx);
+    app.watch_project(&tx);
 
     loop {
-        terminal.draw(|f| ui::draw(f, &app.services, app.selected_index, &app.title, &app.cpu_history, &mut sys))?;
+        terminal.draw(|f| ui::draw(f, &mut app, &mut sys))?;
 
         if let Some(event) = rx.recv().await {
+            if let Some(hub) = &hub {
+                hub.publish(&event);
+            }
             match event {
                 Event::Tick => {
                     app.on_tick(&mut sys);
                 }
-                Event::Key(key) => {
-                    match key.code {
-                        KeyCode::Char('q') => break,
-                        KeyCode::Down | KeyCode::Char('j') => app.next(),
-                        KeyCode::Up | KeyCode::Char('k') => app.previous(),
-                        KeyCode::Enter | KeyCode::Char('s') => {
-                             if let Some(service) = app.services.get_mut(app.selected_index) {
-                                 match service.status {
-                                     ServiceStatus::Stopped | ServiceStatus::Failed | ServiceStatus::Completed => {
-                                         let service_clone = service.clone();
-                                         let pm = process_manager.clone();
-                                         tokio::spawn(async move {
-                                             let _ = pm.spawn_service(service_clone).await;
-                                         });
-                                     },
-                                     ServiceStatus::Running(_) => {}
-                                 }
-                             }
-                        }
-                        _ => {}
-                    }
+                Event::Key(key) if app.popup.is_some() => {
+                    app.handle_popup_key(key);
+                }
+                Event::Key(key) if app.input_mode != InputMode::Normal => {
+                    app.handle_input_key(key);
                 }
-                Event::ServiceLog(name, line) => {
-                    if let Some(service) = app.services.iter_mut().find(|s| s.name == name) {
-                        service.logs.push(line);
+                Event::Key(key) => {
+                    if let Some(action) = app.keymap.action_for(&key) {
+                        app.perform(action);
                     }
                 }
-                Event::ServiceStatus(name, status) => {
-                     if let Some(service) = app.services.iter_mut().find(|s| s.name == name) {
-                        service.status = status;
+                Event::Mouse(mouse) => app.handle_mouse(mouse),
+                Event::ServiceLog(line) => app.push_log(line),
+                Event::ServiceStatus(name, status) => app.set_status(name, status),
+                Event::ServiceExit(..) => {}
+                Event::Message(message) => app.message = Some(message),
+                Event::Control(control) => app.control(control),
+                Event::PortTaken(name, port, holder) => app.port_taken(name, port, holder),
+                Event::FilesChanged(name, paths) => app.files_changed(name, paths),
+                Event::ProjectChanged(paths) => {
+                    app.project_changed(paths, &mut config, &tx);
+                    if let Some(hub) = &hub {
+                        hub.set_services(&app.services);
                     }
                 }
-                Event::Quit => break,
-                _ => {}
+                Event::Quit => app.should_quit = true,
             }
         }
+        if app.should_quit {
+            break;
+        }
+        if let Some(path) = app.edit_file.take() {
+            input_paused.store(true, Ordering::Relaxed);
+            // Let a poll already in progress time out before handing over stdin.
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            disable_raw_mode()?;
+            execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
+            if let Err(e) = open_in_editor(&path).await {
+                app.message = Some(format!("Failed to open {}: {}", path.display(), e));
+            }
+            enable_raw_mode()?;
+            execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)?;
+            terminal.clear()?;
+            input_paused.store(false, Ordering::Relaxed);
+        }
     }
 
+    app.flush_state();
+    runner.shutdown();
+
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
@@ -0,0 +1,332 @@
+use crate::config::LogConfig;
+use crate::models::{LogLine, LogStream};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// A single persisted log file of a service.
+#[derive(Debug, Clone)]
+pub struct RunInfo {
+    pub path: PathBuf,
+    pub file_name: String,
+    pub size: u64,
+    pub modified: DateTime<Local>,
+}
+
+struct RunFile {
+    writer: BufWriter<File>,
+    opened: DateTime<Local>,
+    bytes: u64,
+}
+
+/// Writes service output to `<dir>/<service>/<timestamp>.log`, rotating and
+/// pruning files according to the `logs` section of the config.
+pub struct LogArchive {
+    root: PathBuf,
+    rotate_bytes: u64,
+    rotate_age: chrono::Duration,
+    retain_files: usize,
+    retain_age: Duration,
+    files: HashMap<String, RunFile>,
+}
+
+/// Directory name used for a service, e.g. `web: dev` becomes `web-dev`.
+pub fn service_dir_name(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            out.push(c);
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    out.trim_matches('-').to_string()
+}
+
+impl LogArchive {
+    pub fn new(root: PathBuf, config: &LogConfig) -> Self {
+        Self {
+            root,
+            rotate_bytes: config.rotate_bytes,
+            rotate_age: chrono::Duration::hours(config.rotate_hours as i64),
+            retain_files: config.retain_files.max(1),
+            retain_age: Duration::from_secs(config.retain_days * 24 * 60 * 60),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Close the current file of a service so its next line starts a new run.
+    pub fn begin_run(&mut self, service: &str) {
+        if let Some(mut run) = self.files.remove(service) {
+            let _ = run.writer.flush();
+        }
+    }
+
+    pub fn write(&mut self, line: &LogLine) -> Result<()> {
+        let service = line.service.as_str();
+        let needs_rotation = self.files.get(service).is_some_and(|run| {
+            run.bytes >= self.rotate_bytes || Local::now() - run.opened >= self.rotate_age
+        });
+        if needs_rotation {
+            self.begin_run(service);
+        }
+        if !self.files.contains_key(service) {
+            let run = self.open_run(service)?;
+            self.files.insert(service.to_string(), run);
+        }
+        let Some(run) = self.files.get_mut(service) else {
+            return Ok(());
+        };
+
+        let record = format!(
+            "{} {} {}\n",
+            line.timestamp.to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            line.stream.as_str(),
+            line.raw
+        );
+        run.writer.write_all(record.as_bytes())?;
+        run.bytes += record.len() as u64;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        for run in self.files.values_mut() {
+            run.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn open_run(&self, service: &str) -> Result<RunFile> {
+        let dir = self.root.join(service_dir_name(service));
+        fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+        self.prune(&dir)?;
+
+        let now = Local::now();
+        let stamp = now.format("%Y%m%d-%H%M%S").to_string();
+        // Numbered after every run of the same second, including ones whose
+        // earlier siblings were just pruned, so names keep sorting by age.
+        let runs = list_runs_in(&dir)?;
+        let next = runs
+            .iter()
+            .map(|run| run_order(&run.file_name))
+            .filter(|(s, _)| *s == stamp)
+            .map(|(_, n)| n + 1)
+            .max();
+        let path = match next {
+            Some(n) => dir.join(format!("{}-{}.log", stamp, n)),
+            None => dir.join(format!("{}.log", stamp)),
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("opening {}", path.display()))?;
+        Ok(RunFile {
+            writer: BufWriter::new(file),
+            opened: now,
+            bytes: 0,
+        })
+    }
+
+    /// Delete files beyond the retention count or age, leaving room for the
+    /// run about to be opened.
+    fn prune(&self, dir: &Path) -> Result<()> {
+        let runs = list_runs_in(dir)?;
+        let cutoff = SystemTime::now() - self.retain_age;
+        for (i, run) in runs.iter().enumerate() {
+            let too_old = SystemTime::from(run.modified) < cutoff;
+            if i + 1 >= self.retain_files || too_old {
+                let _ = fs::remove_file(&run.path);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn list_runs_in(dir: &Path) -> Result<Vec<RunInfo>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut runs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("log") {
+            continue;
+        }
+        let meta = entry.metadata()?;
+        runs.push(RunInfo {
+            file_name: entry.file_name().to_string_lossy().into_owned(),
+            path,
+            size: meta.len(),
+            modified: meta.modified()?.into(),
+        });
+    }
+    // Newest first.
+    runs.sort_by(|a, b| run_order(&b.file_name).cmp(&run_order(&a.file_name)));
+    Ok(runs)
+}
+
+/// Sort key of a log file name: its timestamp, then the counter that
+/// `<stamp>-<n>.log` adds for runs started within the same second.
+fn run_order(file_name: &str) -> (&str, u32) {
+    let stem = file_name.strip_suffix(".log").unwrap_or(file_name);
+    // The stamp itself has one dash (`20240102-030405`).
+    match stem.rsplit_once('-') {
+        Some((stamp, n)) if stamp.contains('-') => (stamp, n.parse().unwrap_or(0)),
+        _ => (stem, 0),
+    }
+}
+
+/// Past runs of a service under the log directory, newest first.
+pub fn list_runs(log_dir: &Path, service: &str) -> Result<Vec<RunInfo>> {
+    list_runs_in(&log_dir.join(service_dir_name(service)))
+}
+
+/// Read a persisted log file of `service` back into log lines.
+pub fn read_run(path: &Path, service: &str) -> Result<Vec<LogLine>> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut lines = Vec::new();
+    for raw in BufReader::new(file).lines() {
+        let raw = raw?;
+        let mut parts = raw.splitn(3, ' ');
+        let parsed = match (parts.next(), parts.next(), parts.next()) {
+            (Some(ts), Some(stream), text) => DateTime::parse_from_rfc3339(ts)
+                .ok()
+                .zip(LogStream::parse(stream))
+                .map(|(ts, stream)| LogLine {
+                    timestamp: ts.with_timezone(&Local),
+                    monotonic: Duration::ZERO,
+                    ..LogLine::new(service, stream, text.unwrap_or_default())
+                }),
+            _ => None,
+        };
+        lines.push(parsed.unwrap_or_else(|| LogLine::new(service, LogStream::Stdout, raw)));
+    }
+    Ok(lines)
+}
+
+/// Handler for `devrunner history`: list past runs of a service, or print
+/// one of them (0 being the most recent) to stdout.
+pub fn print_history(log_dir: &Path, service: &str, run: Option<usize>) -> Result<()> {
+    let runs = list_runs(log_dir, service)?;
+    match run {
+        None => {
+            if runs.is_empty() {
+                println!("No persisted logs for '{}' in {}", service, log_dir.display());
+            }
+            for (i, info) in runs.iter().enumerate() {
+                println!(
+                    "{:>3}  {}  {:>10}  {}",
+                    i,
+                    info.modified.format("%Y-%m-%d %H:%M:%S"),
+                    info.size,
+                    info.path.display()
+                );
+            }
+        }
+        Some(n) => {
+            let info = runs
+                .get(n)
+                .with_context(|| format!("'{}' has {} persisted runs, no run #{}", service, runs.len(), n))?;
+            for line in read_run(&info.path, service)? {
+                println!("{} [{}] {}", line.timestamp.format("%Y-%m-%d %H:%M:%S"), line.stream.as_str(), line.raw);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(root: &Path, rotate_bytes: u64, retain_files: usize) -> LogArchive {
+        LogArchive::new(root.to_path_buf(), &LogConfig { rotate_bytes, retain_files, ..LogConfig::default() })
+    }
+
+    fn raws(path: &Path) -> Vec<String> {
+        read_run(path, "web: dev").expect("readable run").into_iter().map(|l| l.raw).collect()
+    }
+
+    #[test]
+    fn service_names_become_directory_names() {
+        assert_eq!(service_dir_name("web: dev"), "web-dev");
+        assert_eq!(service_dir_name("@scope/pkg: build:prod"), "scope-pkg-build-prod");
+        assert_eq!(service_dir_name("api_v2.rs"), "api_v2.rs");
+    }
+
+    #[test]
+    fn runs_of_the_same_second_sort_by_their_counter() {
+        let mut names = ["20240102-030405-1.log", "20240102-030406.log", "20240102-030405.log", "20240102-030405-10.log", "20240102-030405-2.log"];
+        names.sort_by(|a, b| run_order(b).cmp(&run_order(a)));
+        assert_eq!(
+            names,
+            ["20240102-030406.log", "20240102-030405-10.log", "20240102-030405-2.log", "20240102-030405-1.log", "20240102-030405.log"]
+        );
+    }
+
+    #[test]
+    fn lines_are_read_back_as_written() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let mut store = archive(dir.path(), u64::MAX, 10);
+        let out = LogLine::new("web: dev", LogStream::Stdout, "listening on 3000");
+        let err = LogLine::new("web: dev", LogStream::Stderr, "warning: slow");
+        store.write(&out).expect("write");
+        store.write(&err).expect("write");
+        store.flush().expect("flush");
+
+        let runs = list_runs(dir.path(), "web: dev").expect("runs");
+        assert_eq!(runs.len(), 1);
+        assert!(runs[0].path.starts_with(dir.path().join("web-dev")));
+        let lines = read_run(&runs[0].path, "web: dev").expect("readable run");
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].raw.as_str(), lines[0].stream), ("listening on 3000", LogStream::Stdout));
+        assert_eq!((lines[1].raw.as_str(), lines[1].stream), ("warning: slow", LogStream::Stderr));
+        assert_eq!(lines[0].timestamp.timestamp_millis(), out.timestamp.timestamp_millis());
+        assert_eq!(lines[1].service, "web: dev");
+    }
+
+    #[test]
+    fn lines_that_are_not_records_are_kept_as_output() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("run.log");
+        fs::write(&path, "garbage\n2024-01-02T03:04:05.000+00:00 stderr boom\n").expect("write");
+        let lines = read_run(&path, "web: dev").expect("readable run");
+        assert_eq!((lines[0].raw.as_str(), lines[0].stream), ("garbage", LogStream::Stdout));
+        assert_eq!((lines[1].raw.as_str(), lines[1].stream), ("boom", LogStream::Stderr));
+    }
+
+    #[test]
+    fn each_run_gets_its_own_file() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let mut store = archive(dir.path(), u64::MAX, 10);
+        for text in ["first", "second"] {
+            store.begin_run("web: dev");
+            store.write(&LogLine::new("web: dev", LogStream::Stdout, text)).expect("write");
+        }
+        store.flush().expect("flush");
+        let runs = list_runs(dir.path(), "web: dev").expect("runs");
+        assert_eq!(runs.len(), 2);
+        assert_eq!(raws(&runs[0].path), ["second"]);
+        assert_eq!(raws(&runs[1].path), ["first"]);
+    }
+
+    #[test]
+    fn full_files_rotate_and_the_oldest_are_pruned() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        // Every line fills a file, so each write rotates.
+        let mut store = archive(dir.path(), 1, 3);
+        for i in 0..5 {
+            store.write(&LogLine::new("web: dev", LogStream::Stdout, format!("line {}", i))).expect("write");
+        }
+        store.flush().expect("flush");
+        let runs = list_runs(dir.path(), "web: dev").expect("runs");
+        let kept: Vec<Vec<String>> = runs.iter().map(|run| raws(&run.path)).collect();
+        assert_eq!(kept, [["line 4"], ["line 3"], ["line 2"]]);
+    }
+}
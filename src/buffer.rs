@@ -0,0 +1,190 @@
+use crate::logparse::Record;
+use crate::models::LogLine;
+use std::collections::VecDeque;
+use std::mem;
+
+pub const DEFAULT_MAX_LINES: usize = 10_000;
+pub const DEFAULT_MAX_BYTES: usize = 8 * 1024 * 1024;
+
+/// Bounded ring buffer of a service's output.
+///
+/// Every line gets a monotonically increasing sequence number so that
+/// positions held by the UI (search cursor, scroll anchor) stay valid while
+/// old lines are evicted from the front.
+#[derive(Debug, Clone)]
+pub struct LogBuffer {
+    lines: VecDeque<LogLine>,
+    first_seq: u64,
+    dropped: u64,
+    bytes: usize,
+    max_lines: usize,
+    max_bytes: usize,
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LINES, DEFAULT_MAX_BYTES)
+    }
+}
+
+impl LogBuffer {
+    pub fn new(max_lines: usize, max_bytes: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            first_seq: 0,
+            dropped: 0,
+            bytes: 0,
+            max_lines: max_lines.max(1),
+            max_bytes,
+        }
+    }
+
+    /// Approximate heap and inline footprint of a single line.
+    fn cost(line: &LogLine) -> usize {
+        let record = line.record.as_ref().map_or(0, |r| {
+            mem::size_of::<Record>()
+                + r.fields.iter().map(|(k, v)| k.capacity() + v.capacity()).sum::<usize>()
+                + [&r.time, &r.level, &r.msg].iter().map(|s| s.as_ref().map_or(0, String::capacity)).sum::<usize>()
+        });
+        mem::size_of::<LogLine>() + line.raw.capacity() + line.service.capacity() + record
+    }
+
+    pub fn set_limits(&mut self, max_lines: usize, max_bytes: usize) {
+        self.max_lines = max_lines.max(1);
+        self.max_bytes = max_bytes;
+        self.evict();
+    }
+
+    pub fn push(&mut self, line: LogLine) {
+        self.bytes += Self::cost(&line);
+        self.lines.push_back(line);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        // Always keep the newest line, even if it alone exceeds the byte limit.
+        while self.lines.len() > 1 && (self.lines.len() > self.max_lines || self.bytes > self.max_bytes) {
+            if let Some(old) = self.lines.pop_front() {
+                self.bytes -= Self::cost(&old);
+                self.first_seq += 1;
+                self.dropped += 1;
+            }
+        }
+    }
+
+    /// Drop every line; sequence numbers keep counting from where they were.
+    pub fn clear(&mut self) {
+        self.first_seq += self.lines.len() as u64;
+        self.lines.clear();
+        self.bytes = 0;
+        self.dropped = 0;
+    }
+
+    /// Lines paired with their sequence numbers, oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (u64, &LogLine)> {
+        self.lines.iter().enumerate().map(|(i, line)| (self.first_seq + i as u64, line))
+    }
+
+    pub fn get(&self, seq: u64) -> Option<&LogLine> {
+        let idx = seq.checked_sub(self.first_seq)?;
+        self.lines.get(idx as usize)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Sequence number the next pushed line will receive.
+    pub fn end_seq(&self) -> u64 {
+        self.first_seq + self.lines.len() as u64
+    }
+
+    /// Number of lines evicted because a limit was hit.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::LogStream;
+
+    fn line(text: &str) -> LogLine {
+        LogLine::new("web", LogStream::Stdout, text)
+    }
+
+    fn raws(buffer: &LogBuffer) -> Vec<(u64, &str)> {
+        buffer.iter().map(|(seq, line)| (seq, line.raw.as_str())).collect()
+    }
+
+    #[test]
+    fn evicts_oldest_lines_past_max_lines() {
+        let mut buffer = LogBuffer::new(3, usize::MAX);
+        for text in ["a", "b", "c", "d", "e"] {
+            buffer.push(line(text));
+        }
+        assert_eq!(raws(&buffer), [(2, "c"), (3, "d"), (4, "e")]);
+        assert_eq!(buffer.dropped(), 2);
+        assert_eq!(buffer.end_seq(), 5);
+    }
+
+    #[test]
+    fn evicts_past_max_bytes_but_keeps_the_newest_line() {
+        let cost = LogBuffer::cost(&line("a"));
+        let mut buffer = LogBuffer::new(100, cost * 2);
+        for text in ["a", "b", "c"] {
+            buffer.push(line(text));
+        }
+        assert_eq!(raws(&buffer), [(1, "b"), (2, "c")]);
+        assert_eq!(buffer.bytes(), cost * 2);
+
+        let mut tiny = LogBuffer::new(100, 1);
+        tiny.push(line("a"));
+        tiny.push(line("b"));
+        assert_eq!(raws(&tiny), [(1, "b")]);
+    }
+
+    #[test]
+    fn get_looks_up_by_sequence_number() {
+        let mut buffer = LogBuffer::new(2, usize::MAX);
+        for text in ["a", "b", "c"] {
+            buffer.push(line(text));
+        }
+        assert!(buffer.get(0).is_none());
+        assert_eq!(buffer.get(1).map(|l| l.raw.as_str()), Some("b"));
+        assert_eq!(buffer.get(2).map(|l| l.raw.as_str()), Some("c"));
+        assert!(buffer.get(3).is_none());
+    }
+
+    #[test]
+    fn clear_keeps_counting_sequence_numbers() {
+        let mut buffer = LogBuffer::new(10, usize::MAX);
+        buffer.push(line("a"));
+        buffer.push(line("b"));
+        buffer.clear();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.bytes(), 0);
+        buffer.push(line("c"));
+        assert_eq!(raws(&buffer), [(2, "c")]);
+    }
+
+    #[test]
+    fn lowering_limits_evicts_immediately() {
+        let mut buffer = LogBuffer::new(10, usize::MAX);
+        for text in ["a", "b", "c", "d"] {
+            buffer.push(line(text));
+        }
+        buffer.set_limits(1, usize::MAX);
+        assert_eq!(raws(&buffer), [(3, "d")]);
+        assert_eq!(buffer.dropped(), 3);
+    }
+}
@@ -1,14 +1,133 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
     /// Optional path to scan for services
-    #[arg(short, long, default_value = ".")]
+    #[arg(short, long, default_value = ".", global = true)]
     pub path: PathBuf,
 
     /// Optional path to a configuration file
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List or print persisted logs of past runs of a service
+    History {
+        /// Service name as shown in the sidebar, e.g. "web: dev"
+        service: String,
+
+        /// Print the given run (0 is the most recent) instead of listing runs
+        #[arg(short, long)]
+        run: Option<usize>,
+    },
+    /// Print the discovered services
+    List {
+        #[arg(short, long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+    /// Run the services headless in the background, controlled through a
+    /// socket in `.devrunner/`; the TUI attaches to it when started
+    Daemon {
+        /// Detach from the terminal, logging to `.devrunner/daemon.log`
+        #[arg(short, long)]
+        detach: bool,
+    },
+    /// Start services in the running daemon
+    Start {
+        #[arg(required = true)]
+        services: Vec<String>,
+    },
+    /// Stop services in the running daemon
+    Stop {
+        #[arg(required = true)]
+        services: Vec<String>,
+    },
+    /// Restart services in the running daemon
+    Restart {
+        #[arg(required = true)]
+        services: Vec<String>,
+    },
+    /// Show the status of the daemon's services
+    Status,
+    /// Print the buffered logs of a service in the running daemon
+    Logs {
+        service: String,
+
+        /// Number of most recent lines to print
+        #[arg(short = 'n', long, default_value_t = 100)]
+        lines: usize,
+
+        /// Keep printing new lines as they arrive
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Run services without the TUI, streaming their output to stdout.
+    /// Exits with the worst exit code among them
+    Run {
+        /// Service names as shown in the sidebar, e.g. "web: dev"
+        #[arg(required = true)]
+        services: Vec<String>,
+
+        /// Start all services at once (the default)
+        #[arg(long, conflicts_with = "sequential")]
+        parallel: bool,
+
+        /// Start each service once the previous one has exited
+        #[arg(long)]
+        sequential: bool,
+
+        /// Stop the remaining services as soon as one fails
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Write a starter config listing the discovered services
+    Init {
+        #[arg(short, long, value_enum, default_value_t = ConfigFormat::Toml)]
+        format: ConfigFormat,
+
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Work with the config files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print a JSON Schema of the config file, for editor autocompletion
+    Schema,
+    /// Print the effective config, after merging the user, project and local files
+    Show {
+        /// Annotate each value with the file it came from
+        #[arg(long)]
+        origin: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ListFormat {
+    /// Aligned columns for reading
+    Table,
+    /// A single JSON array
+    Json,
+    /// One JSON object per line
+    Ndjson,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
 }
@@ -0,0 +1,12 @@
+use base64::Engine;
+use std::io::{self, Write};
+
+/// Put `text` on the system clipboard with the OSC 52 escape sequence, which
+/// the terminal handles itself, so it also works over SSH and inside tmux
+/// (with `set-clipboard on`).
+pub fn copy(text: &str) -> io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()
+}
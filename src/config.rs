@@ -1,48 +1,740 @@
+use crate::buffer::{DEFAULT_MAX_BYTES, DEFAULT_MAX_LINES};
+use crate::keys::{Action, KeySpec};
+use crate::models::Service;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, Default, JsonSchema)]
 pub struct AppConfig {
+    /// Location of the JSON Schema, for editors; not used by DevRunner.
+    #[serde(rename = "$schema", default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
     #[serde(default)]
     pub ignore_paths: Vec<String>,
     #[serde(default)]
     pub custom_scripts: Vec<CustomScript>,
+    #[serde(default)]
+    pub logs: LogConfig,
+    /// Per-service overrides, keyed by service name (e.g. `"web: dev"`).
+    #[serde(default)]
+    pub services: HashMap<String, ServiceConfig>,
+    /// Keys for TUI actions, replacing the defaults of each listed action.
+    #[serde(default)]
+    pub keybindings: HashMap<Action, KeySpec>,
+    #[serde(default)]
+    pub api: ApiConfig,
+    /// Problems found while loading that didn't stop it, such as unknown
+    /// fields.
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+
+impl AppConfig {
+    /// Effective log buffer limits `(max_lines, max_bytes)` for a service.
+    pub fn log_limits(&self, service: &str) -> (usize, usize) {
+        let overrides = self.services.get(service).map(|s| &s.logs);
+        (
+            overrides.and_then(|l| l.max_lines).unwrap_or(self.logs.max_lines),
+            overrides.and_then(|l| l.max_bytes).unwrap_or(self.logs.max_bytes),
+        )
+    }
+
+    pub fn limits(&self, service: &str) -> LimitsConfig {
+        self.services.get(service).map(|s| s.limits.clone()).unwrap_or_default()
+    }
+
+    pub fn port(&self, service: &str) -> Option<u16> {
+        self.services.get(service).and_then(|s| s.port)
+    }
+
+    pub fn watch(&self, service: &str) -> Option<&WatchConfig> {
+        self.services.get(service).and_then(|s| s.watch.as_ref())
+    }
+
+    /// Apply the per-service settings to a discovered service.
+    pub fn apply(&self, service: &mut Service) {
+        let (max_lines, max_bytes) = self.log_limits(&service.name);
+        service.logs.set_limits(max_lines, max_bytes);
+        service.limits = self.limits(&service.name);
+        service.port = self.port(&service.name);
+        let overrides = self.services.get(&service.name);
+        if let Some(command) = overrides.and_then(|s| s.command.clone()) {
+            service.command = command;
+        }
+        service.env = overrides.map(|s| s.env.clone()).unwrap_or_default();
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct LogConfig {
+    #[serde(default = "default_max_lines")]
+    pub max_lines: usize,
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: usize,
+    /// Write every service's output to disk.
+    #[serde(default)]
+    pub persist: bool,
+    /// Directory for persisted logs, relative to the project root.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// Start a new log file once the current one reaches this size.
+    #[serde(default = "default_rotate_bytes")]
+    pub rotate_bytes: u64,
+    /// Start a new log file once the current one is this many hours old.
+    #[serde(default = "default_rotate_hours")]
+    pub rotate_hours: u64,
+    /// Number of log files kept per service.
+    #[serde(default = "default_retain_files")]
+    pub retain_files: usize,
+    /// Log files older than this many days are deleted.
+    #[serde(default = "default_retain_days")]
+    pub retain_days: u64,
+}
+
+impl LogConfig {
+    pub fn resolve_dir(&self, root_dir: &Path) -> PathBuf {
+        match &self.dir {
+            Some(dir) => root_dir.join(dir),
+            None => root_dir.join(".devrunner").join("logs"),
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            max_lines: DEFAULT_MAX_LINES,
+            max_bytes: DEFAULT_MAX_BYTES,
+            persist: false,
+            dir: None,
+            rotate_bytes: default_rotate_bytes(),
+            rotate_hours: default_rotate_hours(),
+            retain_files: default_retain_files(),
+            retain_days: default_retain_days(),
+        }
+    }
+}
+
+fn default_max_lines() -> usize {
+    DEFAULT_MAX_LINES
+}
+
+fn default_max_bytes() -> usize {
+    DEFAULT_MAX_BYTES
+}
+
+fn default_rotate_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_rotate_hours() -> u64 {
+    24
+}
+
+fn default_retain_files() -> usize {
+    20
+}
+
+fn default_retain_days() -> u64 {
+    7
+}
+
+/// The local HTTP API, off unless `enabled`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ApiConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Port on 127.0.0.1 to listen on.
+    #[serde(default = "default_api_port")]
+    pub port: u16,
+    /// Token clients must send; a random one is generated when unset.
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: default_api_port(),
+            token: None,
+        }
+    }
+}
+
+fn default_api_port() -> u16 {
+    7878
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, JsonSchema)]
+pub struct ServiceConfig {
+    /// Run this instead of the command found in the manifest.
+    pub command: Option<String>,
+    /// Extra environment variables for the service.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub logs: ServiceLogConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    /// TCP port the service listens on, checked before it is started.
+    pub port: Option<u16>,
+    /// Restart the service when its files change.
+    pub watch: Option<WatchConfig>,
+}
+
+/// Files whose changes restart a service.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct WatchConfig {
+    /// Globs relative to the service's directory, e.g. `src/**/*.rs`.
+    pub paths: Vec<String>,
+    /// Globs excluded from `paths`; `.git`, `node_modules`, `target` and
+    /// `.devrunner` are always ignored.
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// How long changes must settle before restarting, in milliseconds.
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+}
+
+fn default_debounce_ms() -> u64 {
+    300
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, JsonSchema)]
+pub struct ServiceLogConfig {
+    pub max_lines: Option<usize>,
+    pub max_bytes: Option<usize>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Resource ceilings and alert thresholds of a service.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default, JsonSchema)]
+pub struct LimitsConfig {
+    /// Memory ceiling in MiB, enforced by the kernel through a cgroup v2
+    /// `memory.max` when a delegated cgroup is available (or `RLIMIT_AS`
+    /// with `memory_rlimit`) unless `restart_on_memory` is set.
+    pub memory_mb: Option<u64>,
+    /// Fall back to `RLIMIT_AS` for `memory_mb` when no cgroup can be
+    /// created. It caps virtual memory, which runtimes that reserve large
+    /// address ranges up front (Node/V8, the JVM, Go) fail at right away.
+    #[serde(default)]
+    pub memory_rlimit: bool,
+    /// CPU time limit in seconds for each process (`RLIMIT_CPU`).
+    pub cpu_seconds: Option<u64>,
+    /// Warn when the process tree's resident memory exceeds this many MiB.
+    pub alert_memory_mb: Option<u64>,
+    /// Warn when the process tree's CPU usage exceeds this percentage.
+    pub alert_cpu_percent: Option<f32>,
+    /// Instead of letting the kernel kill the service at `memory_mb`,
+    /// restart it gracefully once its resident memory goes above it.
+    #[serde(default)]
+    pub restart_on_memory: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct CustomScript {
     pub name: String,
     pub command: String,
 }
 
+/// File names looked up in the project root when no config path is given.
+pub const CONFIG_NAMES: [&str; 6] = [
+    ".devrunner.json",
+    "devrunner.json",
+    ".devrunner.toml",
+    "devrunner.toml",
+    ".devrunner.yaml",
+    "devrunner.yaml",
+];
+
+/// The project config file: `path` if it exists, otherwise the first
+/// default name found in `root_dir`.
+pub fn find_config(path: Option<&Path>, root_dir: &Path) -> Option<PathBuf> {
+    if let Some(p) = path
+        && p.exists()
+    {
+        return Some(p.to_path_buf());
+    }
+    CONFIG_NAMES.iter().map(|name| root_dir.join(name)).find(|p| p.exists())
+}
+
+/// Names of the uncommitted override in the project root, applied on top of
+/// the project config.
+pub const LOCAL_NAMES: [&str; 3] = [".devrunner.local.json", ".devrunner.local.toml", ".devrunner.local.yaml"];
+
+/// Names of the user-level config, applied under every project's config.
+const USER_NAMES: [&str; 3] = ["config.json", "config.toml", "config.yaml"];
+
+/// `$XDG_CONFIG_HOME/devrunner`, or `~/.config/devrunner`.
+fn user_config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("devrunner"))
+}
+
+/// Every file that would be loaded if it existed, whether it does or not.
+pub fn candidate_paths(path: Option<&Path>, root_dir: &Path) -> Vec<PathBuf> {
+    let user = user_config_dir().map(|dir| USER_NAMES.map(|name| dir.join(name)));
+    user.into_iter()
+        .flatten()
+        .chain(path.map(Path::to_path_buf))
+        .chain(CONFIG_NAMES.iter().chain(&LOCAL_NAMES).map(|name| root_dir.join(name)))
+        .collect()
+}
+
+/// The files loaded, lowest precedence first: the user config in
+/// `user_dir`, the project config and the local override, whichever exist.
+pub fn config_files(user_dir: Option<&Path>, path: Option<&Path>, root_dir: &Path) -> Vec<PathBuf> {
+    let first = |dir: &Path, names: &[&str]| names.iter().map(|name| dir.join(name)).find(|p| p.is_file());
+    [
+        user_dir.and_then(|dir| first(dir, &USER_NAMES)),
+        find_config(path, root_dir),
+        first(root_dir, &LOCAL_NAMES),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// The file that last set each value, by key path.
+type Origins = HashMap<Vec<String>, PathBuf>;
+
+/// Every config file deep-merged into one value, with where each value came
+/// from and the warnings of each file.
+fn load_layers(user_dir: Option<&Path>, path: Option<&Path>, root_dir: &Path) -> Result<(serde_json::Value, Origins, Vec<String>)> {
+    let mut merged = serde_json::Value::Object(Default::default());
+    let mut origins = Origins::new();
+    let mut warnings = Vec::new();
+    for file in config_files(user_dir, path, root_dir) {
+        let content = fs::read_to_string(&file).with_context(|| format!("Cannot read {}", file.display()))?;
+        // Each file is checked on its own so errors point into it.
+        warnings.extend(parse_config(&file, &content)?.warnings);
+        let value: serde_json::Value = match Format::detect(&file, &content) {
+            Format::Json => serde_json::from_str(&content)?,
+            Format::Toml => toml::from_str(&content)?,
+            Format::Yaml => serde_yaml::from_str(&content)?,
+        };
+        merge(&mut merged, value, &mut Vec::new(), &file, &mut origins);
+    }
+    Ok((merged, origins, warnings))
+}
+
+/// Merge `overlay` into `base`: tables key by key, anything else (including
+/// lists) replaced whole.
+fn merge(base: &mut serde_json::Value, overlay: serde_json::Value, path: &mut Vec<String>, file: &Path, origins: &mut Origins) {
+    match overlay {
+        serde_json::Value::Object(overlay) => {
+            // Merged key by key even into nothing, so only values the file
+            // sets are attributed to it.
+            if !base.is_object() {
+                *base = serde_json::Value::Object(Default::default());
+            }
+            let base = base.as_object_mut().expect("just made an object");
+            for (key, value) in overlay {
+                path.push(key.clone());
+                merge(base.entry(key).or_insert(serde_json::Value::Null), value, path, file, origins);
+                path.pop();
+            }
+        }
+        overlay => {
+            origins.retain(|p, _| !p.starts_with(path));
+            origins.insert(path.clone(), file.to_path_buf());
+            *base = overlay;
+        }
+    }
+}
+
+/// Load the user config, the project config (`path`, or found in `root_dir`)
+/// and the local override, later ones overriding earlier ones.
 pub fn load_config(path: Option<PathBuf>, root_dir: &Path) -> Result<AppConfig> {
-    if let Some(p) = path {
-        if p.exists() {
-            let content = fs::read_to_string(p)?;
-            // Try parsing as JSON first, then TOML
-            let config: AppConfig = serde_json::from_str(&content).or_else(|_| {
-                toml::from_str(&content)
-            })?;
-            return Ok(config);
-        }
-    }
-
-    let possible_names = [".devrunner.json", "devrunner.json", ".devrunner.toml", "devrunner.toml"];
-    for name in possible_names {
-        let p = root_dir.join(name);
-        if p.exists() {
-            let content = fs::read_to_string(p)?;
-            if name.ends_with(".toml") {
-                let config: AppConfig = toml::from_str(&content)?;
-                return Ok(config);
+    let (merged, _, warnings) = load_layers(user_config_dir().as_deref(), path.as_deref(), root_dir)?;
+    let mut config: AppConfig = serde_json::from_value(merged)?;
+    config.warnings = warnings;
+    Ok(config)
+}
+
+/// Leaf values of `value` as TOML, by dotted key; unset options are left out.
+fn flatten(value: &serde_json::Value, path: &mut Vec<String>, out: &mut Vec<(Vec<String>, String)>) {
+    match value {
+        serde_json::Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                path.push(key.clone());
+                flatten(value, path, out);
+                path.pop();
+            }
+        }
+        serde_json::Value::Null => {}
+        _ => {
+            if let Ok(value) = toml::Value::try_from(value) {
+                out.push((path.clone(), value.to_string()));
+            }
+        }
+    }
+}
+
+/// The effective config as `key = value` lines, each with the file that set
+/// it, or `None` for defaults.
+fn effective_lines(merged: serde_json::Value, origins: &Origins) -> Result<Vec<(String, Option<&Path>)>> {
+    let config: AppConfig = serde_json::from_value(merged)?;
+    let mut entries = Vec::new();
+    flatten(&serde_json::to_value(&config)?, &mut Vec::new(), &mut entries);
+    Ok(entries
+        .into_iter()
+        .map(|(segments, value)| (format!("{} = {}", display_path(&segments), value), origins.get(&segments).map(PathBuf::as_path)))
+        .collect())
+}
+
+/// `devrunner config show`: the effective config as dotted TOML keys, with
+/// `origin` the file each value came from.
+pub fn print_effective(path: Option<&Path>, root_dir: &Path, origin: bool) -> Result<()> {
+    let (merged, origins, _) = load_layers(user_config_dir().as_deref(), path, root_dir)?;
+    let lines = effective_lines(merged, &origins)?;
+    let width = lines.iter().map(|(line, _)| line.chars().count()).max().unwrap_or_default();
+    let mut out = std::io::stdout().lock();
+    for (line, file) in &lines {
+        let result = if origin {
+            let source = file.map_or("default".to_string(), |f| f.strip_prefix(root_dir).unwrap_or(f).display().to_string());
+            writeln!(out, "{:<width$}  # {}", line, source)
+        } else {
+            writeln!(out, "{}", line)
+        };
+        match result {
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// Syntaxes a config file can be written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// From the file extension, or failing that from the content: a JSON
+    /// config is an object, so it starts with `{`; TOML starts with a
+    /// `[table]` or `key = value`, YAML with `key:`.
+    fn detect(path: &Path, content: &str) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => return Format::Json,
+            Some("toml") => return Format::Toml,
+            Some("yaml" | "yml") => return Format::Yaml,
+            _ => {}
+        }
+        let first = content
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#'))
+            .unwrap_or_default();
+        match first.find([':', '=']).map(|i| &first[i..i + 1]) {
+            _ if first.starts_with('{') => Format::Json,
+            _ if first.starts_with('[') => Format::Toml,
+            Some(":") => Format::Yaml,
+            _ => Format::Toml,
+        }
+    }
+}
+
+/// 1-based line and column of a byte offset into `content`.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+/// Parse the config in `content`, read from `path`. Errors point at the
+/// offending line and column; fields the config doesn't know are skipped
+/// and reported in `warnings`.
+pub fn parse_config(path: &Path, content: &str) -> Result<AppConfig> {
+    let mut unknown: Vec<Vec<String>> = Vec::new();
+    let mut record = |p: serde_ignored::Path| {
+        let mut segments = Vec::new();
+        path_segments(&p, &mut segments);
+        unknown.push(segments);
+    };
+    let mut config: AppConfig = match Format::detect(path, content) {
+        Format::Json => {
+            let mut de = serde_json::Deserializer::from_str(content);
+            serde_ignored::deserialize(&mut de, &mut record)
+                .and_then(|config| de.end().map(|_| config))
+                .map_err(|e| {
+                    let message = e.to_string();
+                    // The position is reported in front instead.
+                    let message = message.rsplit_once(" at line ").map_or(message.as_str(), |(m, _)| m);
+                    anyhow!("{}:{}:{}: {}", path.display(), e.line(), e.column(), message)
+                })?
+        }
+        Format::Yaml => serde_ignored::deserialize(serde_yaml::Deserializer::from_str(content), &mut record).map_err(|e| {
+            let (line, column) = e.location().map_or((1, 1), |l| (l.line(), l.column()));
+            let message = e.to_string();
+            let message = message.split_once(" at line ").map_or(message.as_str(), |(m, _)| m);
+            anyhow!("{}:{}:{}: {}", path.display(), line, column, message)
+        })?,
+        Format::Toml => toml::Deserializer::parse(content)
+            .and_then(|de| serde_ignored::deserialize(de, &mut record))
+            .map_err(|e| {
+                let (line, column) = line_column(content, e.span().map_or(0, |s| s.start));
+                anyhow!("{}:{}:{}: {}", path.display(), line, column, e.message().trim_end())
+            })?,
+    };
+    config.warnings = unknown
+        .iter()
+        .map(|segments| {
+            let mut warning = format!("{}: unknown field `{}`", path.display(), display_path(segments));
+            if let Some(suggestion) = suggest_field(segments) {
+                warning.push_str(&format!(", did you mean `{}`?", suggestion));
+            }
+            warning
+        })
+        .collect();
+    Ok(config)
+}
+
+/// Keys and indices leading to a value, without the `Option` and newtype
+/// wrappers that don't appear in the file.
+fn path_segments(path: &serde_ignored::Path, segments: &mut Vec<String>) {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => {}
+        Path::Seq { parent, index } => {
+            path_segments(parent, segments);
+            segments.push(index.to_string());
+        }
+        Path::Map { parent, key } => {
+            path_segments(parent, segments);
+            segments.push(key.clone());
+        }
+        Path::Some { parent } | Path::NewtypeStruct { parent } | Path::NewtypeVariant { parent } => path_segments(parent, segments),
+    }
+}
+
+/// `services."web: dev".limts`
+fn display_path(segments: &[String]) -> String {
+    segments
+        .iter()
+        .map(|s| {
+            if s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                s.clone()
             } else {
-                 let config: AppConfig = serde_json::from_str(&content)?;
-                 return Ok(config);
+                format!("{:?}", s)
             }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// JSON Schema of the config file.
+pub fn schema() -> serde_json::Value {
+    schemars::schema_for!(AppConfig).to_value()
+}
+
+/// `devrunner config schema`
+pub fn print_schema() -> Result<()> {
+    let schema = serde_json::to_string_pretty(&schema())?;
+    match writeln!(std::io::stdout().lock(), "{}", schema) {
+        // Piped into `head` or similar.
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(Into::into),
+    }
+}
+
+/// Follow `$ref`s, and into the non-null branch of an optional value.
+fn resolve<'a>(root: &'a serde_json::Value, schema: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+    if let Some(reference) = schema.get("$ref").and_then(|r| r.as_str()) {
+        let name = reference.strip_prefix("#/$defs/")?;
+        return resolve(root, root.get("$defs")?.get(name)?);
+    }
+    if let Some(variants) = schema.get("anyOf").and_then(|v| v.as_array()) {
+        return variants.iter().find_map(|v| resolve(root, v).filter(|v| v.get("properties").is_some()));
+    }
+    Some(schema)
+}
+
+/// The known field closest in spelling to the last segment, if any is close.
+fn suggest_field(segments: &[String]) -> Option<String> {
+    let (field, parents) = segments.split_last()?;
+    let root = schema();
+    let mut schema = resolve(&root, &root)?;
+    for segment in parents {
+        let next = match schema.get("properties").and_then(|p| p.get(segment)) {
+            Some(property) => property,
+            None => schema.get("additionalProperties").or_else(|| schema.get("items"))?,
+        };
+        schema = resolve(&root, next)?;
+    }
+    schema
+        .get("properties")?
+        .as_object()?
+        .keys()
+        .map(|known| (strsim::jaro_winkler(field, known), known))
+        .filter(|(score, _)| *score > 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, known)| known.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str, content: &str) -> Result<AppConfig> {
+        parse_config(Path::new(name), content)
+    }
+
+    fn error(name: &str, content: &str) -> String {
+        parse(name, content).expect_err("invalid config").to_string()
+    }
+
+    #[test]
+    fn detects_the_format_by_extension_then_content() {
+        assert_eq!(Format::detect(Path::new("a.json"), "x = 1"), Format::Json);
+        assert_eq!(Format::detect(Path::new("a.yml"), "{}"), Format::Yaml);
+        assert_eq!(Format::detect(Path::new("a.toml"), "a: 1"), Format::Toml);
+        assert_eq!(Format::detect(Path::new("cfg"), "# comment\n{\"a\": 1}"), Format::Json);
+        assert_eq!(Format::detect(Path::new("cfg"), "[logs]\npersist = true"), Format::Toml);
+        assert_eq!(Format::detect(Path::new("cfg"), "ignore_paths = [\"a:b\"]"), Format::Toml);
+        assert_eq!(Format::detect(Path::new("cfg"), "logs:\n  persist: true"), Format::Yaml);
+    }
+
+    #[test]
+    fn the_same_config_in_every_format() {
+        let json = r#"{"ignore_paths": ["vendor"], "services": {"web: dev": {"port": 3000}}}"#;
+        let toml = "ignore_paths = [\"vendor\"]\n[services.\"web: dev\"]\nport = 3000\n";
+        let yaml = "ignore_paths: [vendor]\nservices:\n  \"web: dev\":\n    port: 3000\n";
+        for (name, content) in [("a.json", json), ("a.toml", toml), ("a.yaml", yaml)] {
+            let config = parse(name, content).expect("valid config");
+            assert_eq!(config.ignore_paths, ["vendor"], "{}", name);
+            assert_eq!(config.port("web: dev"), Some(3000), "{}", name);
+            assert!(config.warnings.is_empty(), "{}", name);
         }
     }
 
-    Ok(AppConfig::default())
+    #[test]
+    fn errors_point_at_line_and_column() {
+        assert_eq!(
+            error("a.json", "{\n  \"api\": {\"port\": \"x\"}\n}"),
+            "a.json:2:21: invalid type: string \"x\", expected u16"
+        );
+        assert!(error("a.toml", "[api]\nport = \"x\"\n").starts_with("a.toml:2:8: invalid type: string \"x\", expected u16"));
+        assert!(error("a.yaml", "api:\n  port: x\n").starts_with("a.yaml:2:9: "));
+        assert!(error("a.toml", "[api\n").starts_with("a.toml:1:"));
+    }
+
+    #[test]
+    fn unknown_fields_are_warnings_with_suggestions() {
+        let config = parse(
+            ".devrunner.toml",
+            "ignore_path = []\nzzz = 1\n[logs]\nmax_line = 5\n[services.\"web: dev\"]\nlimts = {}\nwatch = { paths = [\"src\"], debounce = 5 }\n",
+        )
+        .expect("valid config");
+        let mut warnings = config.warnings.clone();
+        warnings.sort();
+        assert_eq!(
+            warnings,
+            [
+                ".devrunner.toml: unknown field `ignore_path`, did you mean `ignore_paths`?",
+                ".devrunner.toml: unknown field `logs.max_line`, did you mean `max_lines`?",
+                ".devrunner.toml: unknown field `services.\"web: dev\".limts`, did you mean `limits`?",
+                ".devrunner.toml: unknown field `services.\"web: dev\".watch.debounce`, did you mean `debounce_ms`?",
+                ".devrunner.toml: unknown field `zzz`",
+            ]
+        );
+    }
+
+    #[test]
+    fn schema_key_is_accepted() {
+        let config = parse("a.json", r#"{"$schema": "./devrunner.schema.json"}"#).expect("valid config");
+        assert!(config.warnings.is_empty());
+    }
+
+    #[test]
+    fn suggestions_follow_the_schema() {
+        assert_eq!(suggest_field(&["api".into(), "enabld".into()]).as_deref(), Some("enabled"));
+        assert_eq!(suggest_field(&["custom_scripts".into(), "0".into(), "comand".into()]).as_deref(), Some("command"));
+        assert_eq!(suggest_field(&["api".into(), "completely_different".into()]), None);
+    }
+
+    fn layer(origins: &mut Origins, merged: &mut serde_json::Value, file: &str, content: &str) {
+        let value: serde_json::Value = toml::from_str(content).expect("valid TOML");
+        merge(merged, value, &mut Vec::new(), Path::new(file), origins);
+    }
+
+    fn origin_of<'a>(lines: &'a [(String, Option<&Path>)], key: &str) -> Option<(&'a str, Option<&'a Path>)> {
+        lines.iter().find(|(line, _)| line.starts_with(&format!("{} = ", key))).map(|(line, file)| (line.as_str(), *file))
+    }
+
+    #[test]
+    fn layers_merge_tables_and_replace_everything_else() {
+        let mut merged = serde_json::Value::Object(Default::default());
+        let mut origins = Origins::new();
+        layer(&mut origins, &mut merged, "user.toml", "ignore_paths = [\"a\"]\n[logs]\nmax_lines = 500\npersist = true\n");
+        layer(
+            &mut origins,
+            &mut merged,
+            "project.toml",
+            "ignore_paths = [\"b\"]\n[logs]\nmax_lines = 2000\n[services.\"web: dev\"]\ncommand = \"vite\"\nenv = { A = \"1\" }\n",
+        );
+        layer(&mut origins, &mut merged, "local.toml", "[services.\"web: dev\"]\nenv = { B = \"2\" }\nport = 3000\n");
+
+        let lines = effective_lines(merged, &origins).expect("valid config");
+        let file = |key: &str| origin_of(&lines, key).and_then(|(_, f)| f).and_then(|f| f.to_str());
+        assert_eq!(origin_of(&lines, "ignore_paths").map(|(l, _)| l), Some("ignore_paths = [\"b\"]"));
+        assert_eq!(file("ignore_paths"), Some("project.toml"));
+        assert_eq!(origin_of(&lines, "logs.max_lines").map(|(l, _)| l), Some("logs.max_lines = 2000"));
+        assert_eq!(file("logs.max_lines"), Some("project.toml"));
+        assert_eq!(file("logs.persist"), Some("user.toml"));
+        assert_eq!(file("services.\"web: dev\".command"), Some("project.toml"));
+        assert_eq!(file("services.\"web: dev\".env.A"), Some("project.toml"));
+        assert_eq!(file("services.\"web: dev\".env.B"), Some("local.toml"));
+        assert_eq!(file("services.\"web: dev\".port"), Some("local.toml"));
+        // Defaults, including those inside a table a file does set.
+        assert_eq!(origin_of(&lines, "logs.max_bytes").map(|(_, f)| f), Some(None));
+        assert_eq!(origin_of(&lines, "api.port").map(|(_, f)| f), Some(None));
+    }
+
+    #[test]
+    fn replacing_a_table_drops_the_origins_below_it() {
+        let mut merged = serde_json::json!({});
+        let mut origins = Origins::new();
+        merge(&mut merged, serde_json::json!({"a": {"b": 1, "c": 2}}), &mut Vec::new(), Path::new("one"), &mut origins);
+        merge(&mut merged, serde_json::json!({"a": 3}), &mut Vec::new(), Path::new("two"), &mut origins);
+        assert_eq!(merged, serde_json::json!({"a": 3}));
+        assert_eq!(origins.len(), 1);
+        assert_eq!(origins.get(&vec!["a".to_string()]).map(PathBuf::as_path), Some(Path::new("two")));
+    }
+
+    #[test]
+    fn local_override_applies_over_the_project_and_user_configs() {
+        let user = tempfile::tempdir().expect("user config dir");
+        let root = tempfile::tempdir().expect("project dir");
+        let (user, root) = (user.path(), root.path());
+        fs::write(user.join("config.json"), r#"{"logs": {"max_lines": 500}, "services": {"web: dev": {"port": 2000}}}"#).expect("write");
+        fs::write(root.join(".devrunner.toml"), "[services.\"web: dev\"]\ncommand = \"vite\"\nport = 3000\n").expect("write");
+        fs::write(root.join(".devrunner.local.yaml"), "services:\n  \"web: dev\":\n    port: 4000\n    limts: {}\n").expect("write");
+
+        let files = config_files(Some(user), None, root);
+        assert_eq!(files, [user.join("config.json"), root.join(".devrunner.toml"), root.join(".devrunner.local.yaml")]);
+        let (merged, _, warnings) = load_layers(Some(user), None, root).expect("valid config");
+        let config: AppConfig = serde_json::from_value(merged).expect("valid config");
+        assert_eq!(config.logs.max_lines, 500);
+        assert_eq!(config.services["web: dev"].command.as_deref(), Some("vite"));
+        assert_eq!(config.port("web: dev"), Some(4000));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains(".devrunner.local.yaml: unknown field"));
+
+        // Without a user config only the project's files apply.
+        let files = config_files(None, None, root);
+        assert_eq!(files, [root.join(".devrunner.toml"), root.join(".devrunner.local.yaml")]);
+    }
 }
@@ -0,0 +1,662 @@
+use crate::api::{self, Control};
+use crate::archive::LogArchive;
+use crate::cli::Command;
+use crate::config::AppConfig;
+use crate::events::Event;
+use crate::limits::Watchdog;
+use crate::metrics::{self, Sample};
+use crate::models::{LogLine, Service, ServiceStatus};
+use crate::process::ProcessManager;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use sysinfo::System;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::OwnedReadHalf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+/// How long a stopping daemon waits for its services to exit.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(6);
+
+/// How often the services' resource usage is checked against their limits.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sent by a client as a single JSON line; the daemon answers with one or
+/// more `Response` lines and closes the connection (except when streaming).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub enum Request {
+    Start { service: String },
+    Stop { service: String },
+    Restart { service: String },
+    Status,
+    /// The last `lines` lines of a service, then new ones if `follow`.
+    Logs { service: String, lines: usize, follow: bool },
+    /// Every service's status and buffered logs, then every change.
+    Attach,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum Response {
+    Ok,
+    Error { message: String },
+    Services { services: Vec<ServiceState> },
+    Log { line: LogLine },
+    Status { service: String, status: ServiceStatus },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceState {
+    pub name: String,
+    pub status: ServiceStatus,
+}
+
+pub fn socket_path(root_dir: &Path) -> PathBuf {
+    root_dir.join(".devrunner").join("daemon.sock")
+}
+
+/// Send `request` to the daemon serving `root_dir` and return its responses.
+pub async fn call(root_dir: &Path, request: &Request) -> Result<Responses> {
+    let socket = socket_path(root_dir);
+    let stream = UnixStream::connect(&socket).await.with_context(|| {
+        format!("No daemon running for {} (start one with `devrunner daemon`)", root_dir.display())
+    })?;
+    let (read, mut write) = stream.into_split();
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    write.write_all(line.as_bytes()).await?;
+    Ok(Responses(BufReader::new(read).lines()))
+}
+
+/// Responses to a request, read one line at a time.
+pub struct Responses(Lines<BufReader<OwnedReadHalf>>);
+
+impl Responses {
+    pub async fn next(&mut self) -> Result<Option<Response>> {
+        match self.0.next_line().await? {
+            Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The TUI's handle on a daemon it is attached to.
+#[derive(Clone)]
+pub struct Client {
+    root_dir: PathBuf,
+    event_tx: UnboundedSender<Event>,
+}
+
+impl Client {
+    /// Attach to the daemon serving `root_dir`, if one is running, and feed
+    /// its state and every later change into `event_tx`.
+    pub async fn attach(root_dir: &Path, event_tx: UnboundedSender<Event>) -> Option<Self> {
+        let mut responses = call(root_dir, &Request::Attach).await.ok()?;
+        let tx = event_tx.clone();
+        tokio::spawn(async move {
+            loop {
+                let event = match responses.next().await {
+                    Ok(Some(Response::Status { service, status })) => Event::ServiceStatus(service, status),
+                    Ok(Some(Response::Log { mut line })) => {
+                        // The daemon's clock started elsewhere; order by arrival.
+                        line.monotonic = crate::models::monotonic_now();
+                        Event::ServiceLog(line)
+                    }
+                    Ok(Some(_)) => continue,
+                    Ok(None) | Err(_) => {
+                        let _ = tx.send(Event::Message("Lost connection to the daemon".to_string()));
+                        break;
+                    }
+                };
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+        Some(Self { root_dir: root_dir.to_path_buf(), event_tx })
+    }
+
+    /// Send a request, reporting any error back to the TUI.
+    pub async fn request(&self, request: Request) {
+        let result = async {
+            let mut responses = call(&self.root_dir, &request).await?;
+            while let Some(response) = responses.next().await? {
+                if let Response::Error { message } = response {
+                    bail!(message);
+                }
+            }
+            Ok(())
+        };
+        if let Err(e) = result.await {
+            let _ = self.event_tx.send(Event::Message(e.to_string()));
+        }
+    }
+
+    /// Like `request`, without waiting for the answer.
+    pub fn send(&self, request: Request) {
+        let client = self.clone();
+        tokio::spawn(async move { client.request(request).await });
+    }
+}
+
+/// State shared between the daemon's connections and its event loop.
+struct Daemon {
+    services: Vec<Service>,
+    process_manager: Arc<ProcessManager>,
+    pending_restart: HashSet<String>,
+    watchdog: Watchdog,
+    archive: Option<LogArchive>,
+    /// `Log` and `Status` responses for attached and following clients.
+    updates: broadcast::Sender<Response>,
+}
+
+impl Daemon {
+    fn service(&self, name: &str) -> Result<&Service> {
+        self.services.iter().find(|s| s.name == name).with_context(|| format!("Unknown service '{}'", name))
+    }
+
+    fn handle_event(&mut self, event: Event) -> Option<Service> {
+        match event {
+            Event::ServiceLog(line) => {
+                if let Some(store) = &mut self.archive
+                    && let Err(e) = store.write(&line)
+                {
+                    eprintln!("Log persistence disabled: {}", e);
+                    self.archive = None;
+                }
+                let service = self.services.iter_mut().find(|s| s.name == line.service)?;
+                service.logs.push(line.clone());
+                let _ = self.updates.send(Response::Log { line });
+                None
+            }
+            Event::ServiceStatus(name, status) => {
+                let service = self.services.iter_mut().find(|s| s.name == name)?;
+                let starting = matches!(status, ServiceStatus::Running(_)) && !matches!(service.status, ServiceStatus::Running(_));
+                if starting && let Some(store) = &mut self.archive {
+                    store.begin_run(&name);
+                }
+                service.status = status.clone();
+                self.watchdog.status_changed(&name, &status);
+                let _ = self.updates.send(Response::Status { service: name.clone(), status: status.clone() });
+                // Restarted services are spawned again once they have exited.
+                if !matches!(status, ServiceStatus::Running(_)) && self.pending_restart.remove(&name) {
+                    return Some(service.clone());
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Record a round of samples and enforce the services' limits, whether
+    /// or not a TUI is attached.
+    fn check_limits(&mut self, samples: Vec<Sample>) {
+        metrics::record(&mut self.services, samples);
+        let (lines, restarts) = self.watchdog.check(&mut self.services);
+        for line in lines {
+            self.handle_event(Event::ServiceLog(line));
+        }
+        for index in restarts {
+            let name = self.services[index].name.clone();
+            if self.process_manager.stop_service(&name) {
+                self.pending_restart.insert(name);
+            }
+        }
+    }
+
+    fn statuses(&self) -> Vec<ServiceState> {
+        self.services
+            .iter()
+            .map(|s| ServiceState { name: s.name.clone(), status: s.status.clone() })
+            .collect()
+    }
+}
+
+type Shared = Arc<Mutex<Daemon>>;
+
+/// Listen on the control socket, replacing one left behind by a daemon that
+/// didn't exit cleanly. Anyone who can connect can run commands as us, so
+/// the socket is private to our user.
+fn bind(socket: &Path) -> Result<UnixListener> {
+    std::fs::create_dir_all(socket.parent().expect("socket is in a directory"))?;
+    let _ = std::fs::remove_file(socket);
+    let listener = UnixListener::bind(socket).with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    std::fs::set_permissions(socket, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Run services headless for `root_dir` until interrupted, serving requests
+/// on the control socket.
+pub async fn run(services: Vec<Service>, config: &AppConfig, root_dir: &Path) -> Result<()> {
+    let socket = socket_path(root_dir);
+    if UnixStream::connect(&socket).await.is_ok() {
+        bail!("A daemon is already running for {}", root_dir.display());
+    }
+    let listener = bind(&socket)?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let hub = if config.api.enabled {
+        match api::serve(&config.api, root_dir, &services, tx.clone()).await {
+            Ok(hub) => Some(hub),
+            Err(e) => {
+                eprintln!("HTTP API disabled: {:#}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let process_manager = Arc::new(ProcessManager::new(tx));
+    let (updates, _) = broadcast::channel(4096);
+    let daemon: Shared = Arc::new(Mutex::new(Daemon {
+        services,
+        process_manager: process_manager.clone(),
+        pending_restart: HashSet::new(),
+        watchdog: Watchdog::default(),
+        archive: config.logs.persist.then(|| LogArchive::new(config.logs.resolve_dir(root_dir), &config.logs)),
+        updates,
+    }));
+    println!("DevRunner daemon for {} listening on {}", root_dir.display(), socket.display());
+
+    let mut flush = tokio::time::interval(Duration::from_secs(1));
+    let mut sample = tokio::time::interval(SAMPLE_INTERVAL);
+    let mut sys = System::new();
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+    loop {
+        tokio::select! {
+            Some(event) = rx.recv() => {
+                if let Some(hub) = &hub {
+                    hub.publish(&event);
+                }
+                if let Event::Control(control) = event {
+                    let request = match control {
+                        Control::Start(service) => Request::Start { service },
+                        Control::Stop(service) => Request::Stop { service },
+                        Control::Restart(service) => Request::Restart { service },
+                    };
+                    let daemon = daemon.clone();
+                    tokio::spawn(async move { respond(&daemon, request).await });
+                    continue;
+                }
+                let restart = daemon.lock().unwrap().handle_event(event);
+                if let Some(service) = restart {
+                    process_manager.spawn_service(service).await?;
+                }
+            }
+            accepted = listener.accept() => {
+                if let Ok((stream, _)) = accepted {
+                    let daemon = daemon.clone();
+                    tokio::spawn(async move {
+                        // Clients going away mid-stream is how attaching ends.
+                        if let Err(e) = serve(stream, daemon).await
+                            && e.downcast_ref::<std::io::Error>().is_none_or(|e| e.kind() != std::io::ErrorKind::BrokenPipe)
+                        {
+                            eprintln!("Client error: {}", e);
+                        }
+                    });
+                }
+            }
+            _ = flush.tick() => {
+                let mut daemon = daemon.lock().unwrap();
+                if let Some(store) = &mut daemon.archive
+                    && let Err(e) = store.flush()
+                {
+                    eprintln!("Log persistence disabled: {}", e);
+                    daemon.archive = None;
+                }
+            }
+            _ = sample.tick() => {
+                let roots = metrics::running(&daemon.lock().unwrap().services);
+                let samples;
+                (sys, samples) = metrics::sample_blocking(sys, roots).await;
+                daemon.lock().unwrap().check_limits(samples);
+            }
+            _ = tokio::signal::ctrl_c() => break,
+            _ = terminate.recv() => break,
+        }
+    }
+
+    println!("Stopping services...");
+    let _ = std::fs::remove_file(&socket);
+    process_manager.shutdown();
+    // Give the services time to exit and their final output to be archived.
+    let _ = tokio::time::timeout(SHUTDOWN_TIMEOUT, async {
+        while let Some(event) = rx.recv().await {
+            let mut daemon = daemon.lock().unwrap();
+            daemon.handle_event(event);
+            if !daemon.services.iter().any(|s| matches!(s.status, ServiceStatus::Running(_))) {
+                break;
+            }
+        }
+    })
+    .await;
+    if let Some(store) = &mut daemon.lock().unwrap().archive {
+        let _ = store.flush();
+    }
+    Ok(())
+}
+
+/// Start the daemon in the background, detached from the terminal, logging
+/// to `.devrunner/daemon.log`.
+pub fn spawn_detached(root_dir: &Path, config: Option<&Path>) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let dir = root_dir.join(".devrunner");
+    std::fs::create_dir_all(&dir)?;
+    let log = std::fs::OpenOptions::new().create(true).append(true).open(dir.join("daemon.log"))?;
+    let mut command = Command::new(std::env::current_exe()?);
+    command.arg("--path").arg(root_dir);
+    if let Some(config) = config {
+        command.arg("--config").arg(config);
+    }
+    command
+        .arg("daemon")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    // A session of its own, so closing the terminal doesn't hang it up.
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+    let child = command.spawn()?;
+    println!("Started daemon (pid {}), logging to {}", child.id(), dir.join("daemon.log").display());
+    Ok(())
+}
+
+async fn serve(stream: UnixStream, daemon: Shared) -> Result<()> {
+    let (read, mut write) = stream.into_split();
+    let Some(line) = BufReader::new(read).lines().next_line().await? else {
+        return Ok(());
+    };
+    let request: Request = serde_json::from_str(&line)?;
+    let (responses, updates) = respond(&daemon, request).await;
+    let mut send = async |response: &Response| -> Result<()> {
+        let mut line = serde_json::to_string(response)?;
+        line.push('\n');
+        write.write_all(line.as_bytes()).await?;
+        Ok(())
+    };
+    for response in &responses {
+        send(response).await?;
+    }
+    let Some((mut updates, only)) = updates else {
+        return Ok(());
+    };
+    loop {
+        match updates.recv().await {
+            Ok(response) => {
+                let wanted = match (&only, &response) {
+                    (Some(name), Response::Log { line }) => line.service == *name,
+                    (Some(_), _) => false,
+                    (None, _) => true,
+                };
+                if wanted {
+                    send(&response).await?;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+/// Stream of further updates for a request, limited to one service's logs
+/// when a name is given.
+type Subscription = (broadcast::Receiver<Response>, Option<String>);
+
+async fn start(daemon: &Shared, name: &str) -> Result<()> {
+    let (process_manager, service) = {
+        let daemon = daemon.lock().unwrap();
+        let service = daemon.service(name)?;
+        if matches!(service.status, ServiceStatus::Running(_)) {
+            bail!("{} is already running", name);
+        }
+        (daemon.process_manager.clone(), service.clone())
+    };
+    process_manager.spawn_service(service).await
+}
+
+async fn respond(daemon: &Shared, request: Request) -> (Vec<Response>, Option<Subscription>) {
+    let error = |e: anyhow::Error| (vec![Response::Error { message: e.to_string() }], None);
+    match request {
+        Request::Start { service } => match start(daemon, &service).await {
+            Ok(()) => (vec![Response::Ok], None),
+            Err(e) => error(e),
+        },
+        Request::Stop { service } => {
+            let mut daemon = daemon.lock().unwrap();
+            let result = daemon.service(&service).map(|s| s.status.clone());
+            daemon.pending_restart.remove(&service);
+            match result {
+                Ok(ServiceStatus::Running(_)) if daemon.process_manager.stop_service(&service) => (vec![Response::Ok], None),
+                Ok(ServiceStatus::Running(_)) => error(anyhow::anyhow!("{} is still starting; try again", service)),
+                Ok(_) => error(anyhow::anyhow!("{} is not running", service)),
+                Err(e) => error(e),
+            }
+        }
+        Request::Restart { service } => {
+            let result = {
+                let mut daemon = daemon.lock().unwrap();
+                match daemon.service(&service).map(|s| s.status.clone()) {
+                    Ok(ServiceStatus::Running(_)) if daemon.process_manager.stop_service(&service) => {
+                        daemon.pending_restart.insert(service);
+                        return (vec![Response::Ok], None);
+                    }
+                    Ok(ServiceStatus::Running(_)) => Err(anyhow::anyhow!("{} is still starting; try again", service)),
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e),
+                }
+            };
+            match result {
+                Ok(()) => match start(daemon, &service).await {
+                    Ok(()) => (vec![Response::Ok], None),
+                    Err(e) => error(e),
+                },
+                Err(e) => error(e),
+            }
+        }
+        Request::Status => (vec![Response::Services { services: daemon.lock().unwrap().statuses() }], None),
+        Request::Logs { service, lines, follow } => {
+            let daemon = daemon.lock().unwrap();
+            let logs = match daemon.service(&service) {
+                Ok(s) => &s.logs,
+                Err(e) => return error(e),
+            };
+            let skip = logs.len().saturating_sub(lines);
+            let responses = logs.iter().skip(skip).map(|(_, line)| Response::Log { line: line.clone() }).collect();
+            // Subscribing under the lock means no line is missed or repeated.
+            (responses, follow.then(|| (daemon.updates.subscribe(), Some(service))))
+        }
+        Request::Attach => {
+            let daemon = daemon.lock().unwrap();
+            let mut responses: Vec<Response> = daemon
+                .services
+                .iter()
+                .map(|s| Response::Status { service: s.name.clone(), status: s.status.clone() })
+                .collect();
+            let mut lines: Vec<&LogLine> = daemon.services.iter().flat_map(|s| s.logs.iter().map(|(_, line)| line)).collect();
+            lines.sort_by_key(|line| line.monotonic);
+            responses.extend(lines.into_iter().map(|line| Response::Log { line: line.clone() }));
+            (responses, Some((daemon.updates.subscribe(), None)))
+        }
+    }
+}
+
+/// The requests a CLI command sends to the daemon, if it is one of the
+/// commands that talk to it.
+pub fn requests_for(command: &Command) -> Option<Vec<Request>> {
+    let each = |services: &[String], request: fn(String) -> Request| services.iter().cloned().map(request).collect();
+    Some(match command {
+        Command::Start { services } => each(services, |service| Request::Start { service }),
+        Command::Stop { services } => each(services, |service| Request::Stop { service }),
+        Command::Restart { services } => each(services, |service| Request::Restart { service }),
+        Command::Status => vec![Request::Status],
+        Command::Logs { service, lines, follow } => vec![Request::Logs { service: service.clone(), lines: *lines, follow: *follow }],
+        _ => return None,
+    })
+}
+
+/// Send CLI requests to the daemon one after another, printing the answers.
+pub async fn control(root_dir: &Path, requests: Vec<Request>) -> Result<()> {
+    for request in requests {
+        let mut responses = call(root_dir, &request).await?;
+        while let Some(response) = responses.next().await? {
+            match response {
+                Response::Ok => match &request {
+                    Request::Start { service } => println!("Starting {}", service),
+                    Request::Stop { service } => println!("Stopping {}", service),
+                    Request::Restart { service } => println!("Restarting {}", service),
+                    _ => {}
+                },
+                Response::Error { message } => bail!(message),
+                Response::Services { services } => {
+                    let width = services.iter().map(|s| s.name.chars().count()).max().unwrap_or(0).max(4);
+                    println!("{:<width$}  STATUS", "NAME");
+                    for service in services {
+                        let status = match service.status {
+                            ServiceStatus::Running(pid) => format!("running (pid {})", pid),
+                            ServiceStatus::Stopped => "stopped".to_string(),
+                            ServiceStatus::Failed => "failed".to_string(),
+                            ServiceStatus::Completed => "completed".to_string(),
+                        };
+                        println!("{:<width$}  {}", service.name, status);
+                    }
+                }
+                Response::Log { line } => {
+                    println!("{} [{}] {}", line.timestamp.format("%Y-%m-%d %H:%M:%S"), line.stream.as_str(), line.raw);
+                }
+                Response::Status { .. } => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{LogStream, ProjectType};
+
+    fn daemon(services: Vec<Service>) -> Shared {
+        let (tx, _) = mpsc::unbounded_channel();
+        let (updates, _) = broadcast::channel(16);
+        Arc::new(Mutex::new(Daemon {
+            services,
+            process_manager: Arc::new(ProcessManager::new(tx)),
+            pending_restart: HashSet::new(),
+            watchdog: Watchdog::default(),
+            archive: None,
+            updates,
+        }))
+    }
+
+    fn service(name: &str, lines: &[&str]) -> Service {
+        let mut service = Service::new(name.to_string(), "/repo".into(), "/repo/package.json".into(), ProjectType::Node, String::new());
+        for line in lines {
+            service.logs.push(LogLine::new(name, LogStream::Stdout, *line));
+        }
+        service
+    }
+
+    /// Write `request` to a connection served by `daemon` and collect every
+    /// response line until it is closed.
+    async fn exchange(daemon: Shared, request: &str) -> (Vec<Response>, Result<()>) {
+        let (client, server) = UnixStream::pair().expect("socket pair");
+        let served = tokio::spawn(serve(server, daemon));
+        let (read, mut write) = client.into_split();
+        write.write_all(request.as_bytes()).await.expect("write request");
+        let mut responses = Responses(BufReader::new(read).lines());
+        let mut received = Vec::new();
+        while let Some(response) = responses.next().await.expect("response line") {
+            received.push(response);
+        }
+        (received, served.await.expect("serve task"))
+    }
+
+    #[test]
+    fn requests_are_tagged_json_lines() {
+        let request = Request::Logs { service: "web".to_string(), lines: 5, follow: true };
+        assert_eq!(
+            serde_json::to_string(&request).expect("serializes"),
+            r#"{"request":"logs","service":"web","lines":5,"follow":true}"#
+        );
+        let status: Request = serde_json::from_str(r#"{"request":"status"}"#).expect("parses");
+        assert!(matches!(status, Request::Status));
+        let ok = serde_json::to_string(&Response::Ok).expect("serializes");
+        assert_eq!(ok, r#"{"response":"ok"}"#);
+    }
+
+    #[tokio::test]
+    async fn status_is_answered_and_the_connection_closed() {
+        let (responses, result) = exchange(daemon(vec![service("web", &[])]), "{\"request\":\"status\"}\n").await;
+        result.expect("served");
+        let [Response::Services { services }] = responses.as_slice() else {
+            panic!("unexpected responses: {:?}", responses);
+        };
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].name, "web");
+        assert_eq!(services[0].status, ServiceStatus::Stopped);
+    }
+
+    #[tokio::test]
+    async fn logs_returns_the_most_recent_lines() {
+        let shared = daemon(vec![service("web", &["one", "two", "three"])]);
+        let (responses, result) = exchange(shared, "{\"request\":\"logs\",\"service\":\"web\",\"lines\":2,\"follow\":false}\n").await;
+        result.expect("served");
+        let lines: Vec<&str> = responses
+            .iter()
+            .map(|r| match r {
+                Response::Log { line } => line.raw.as_str(),
+                other => panic!("unexpected response: {:?}", other),
+            })
+            .collect();
+        assert_eq!(lines, ["two", "three"]);
+    }
+
+    #[tokio::test]
+    async fn errors_are_reported_as_responses() {
+        let (responses, result) = exchange(daemon(Vec::new()), "{\"request\":\"stop\",\"service\":\"nope\"}\n").await;
+        result.expect("served");
+        assert!(matches!(responses.as_slice(), [Response::Error { message }] if message == "Unknown service 'nope'"));
+        // A line that isn't a request ends the connection without an answer.
+        let (responses, result) = exchange(daemon(Vec::new()), "hello\n").await;
+        assert!(responses.is_empty());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn cli_commands_map_to_requests() {
+        let requests = requests_for(&Command::Restart { services: vec!["a".to_string(), "b".to_string()] }).expect("daemon command");
+        let names: Vec<&str> = requests
+            .iter()
+            .map(|r| match r {
+                Request::Restart { service } => service.as_str(),
+                other => panic!("unexpected request: {:?}", other),
+            })
+            .collect();
+        assert_eq!(names, ["a", "b"]);
+        assert!(requests_for(&Command::Daemon { detach: false }).is_none());
+    }
+
+    #[tokio::test]
+    async fn the_socket_is_private() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let socket = socket_path(dir.path());
+        std::fs::create_dir_all(socket.parent().expect("parent")).expect("create dir");
+        std::fs::write(&socket, "stale").expect("write stale socket");
+        let _listener = bind(&socket).expect("bind");
+        let mode = std::fs::metadata(&socket).expect("socket metadata").permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
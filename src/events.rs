@@ -1,13 +1,27 @@
-use crate::models::ServiceStatus;
+use crate::api::Control;
+use crate::models::{LogLine, ServiceStatus};
+use crate::ports::Holder;
 
 #[derive(Debug, Clone)]
 pub enum Event {
     Tick,
     Key(crossterm::event::KeyEvent),
-    #[allow(dead_code)]
     Mouse(crossterm::event::MouseEvent),
-    ServiceLog(String, String), // Service Name, Log Line
+    ServiceLog(LogLine),
     ServiceStatus(String, ServiceStatus), // Service Name, New Status
+    /// Exit code of a run that ended on its own, sent just before its final
+    /// status; `None` when it was killed by a signal.
+    ServiceExit(String, Option<i32>),
+    /// Status line text for the TUI, e.g. an error from the daemon.
+    Message(String),
+    /// Watched files of a service changed; paths are relative to its directory.
+    FilesChanged(String, Vec<std::path::PathBuf>),
+    /// Manifests, the config file or project directories changed.
+    ProjectChanged(Vec<std::path::PathBuf>),
+    /// A start, stop or restart request from the HTTP API.
+    Control(Control),
+    /// A service was not started because its port is held by another process.
+    PortTaken(String, u16, Holder),
     #[allow(dead_code)]
     Quit,
 }
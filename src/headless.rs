@@ -0,0 +1,247 @@
+use crate::events::Event;
+use crate::limits::Watchdog;
+use crate::metrics;
+use crate::models::{LogLine, LogStream, Service, ServiceStatus};
+use crate::process::{self, ProcessManager};
+use crate::ui;
+use anyhow::{bail, Result};
+use ratatui::crossterm::style::{Color, Stylize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{IsTerminal, Write};
+use std::sync::Arc;
+use std::time::Duration;
+use sysinfo::System;
+use tokio::sync::mpsc;
+
+/// Exit code reported when the run was interrupted with Ctrl+C.
+const INTERRUPTED: i32 = 130;
+
+/// How often the services' resource usage is checked against their limits.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Prints service output as `name | line`, padded and colored per service.
+struct Printer {
+    width: usize,
+    colors: HashMap<String, Color>,
+    color: bool,
+}
+
+impl Printer {
+    fn line(&self, line: &LogLine) {
+        let prefix = format!("{:<width$} |", line.service, width = self.width);
+        let mut out = std::io::stdout().lock();
+        let _ = if !self.color {
+            writeln!(out, "{} {}", prefix, line.raw)
+        } else if line.stream == LogStream::System {
+            writeln!(out, "{} {}", prefix.with(self.colors[&line.service]), line.raw.as_str().dim())
+        } else {
+            writeln!(out, "{} {}", prefix.with(self.colors[&line.service]), line.raw)
+        };
+    }
+
+    /// A message from DevRunner itself rather than from a service.
+    fn note(&self, text: &str) {
+        if self.color {
+            println!("{}", text.dim());
+        } else {
+            println!("{}", text);
+        }
+    }
+}
+
+/// Ctrl+C presses, registered once so that none is missed while an event
+/// is being handled.
+#[cfg(unix)]
+fn interrupts() -> Result<tokio::signal::unix::Signal> {
+    Ok(tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())?)
+}
+
+#[cfg(windows)]
+fn interrupts() -> Result<tokio::signal::windows::CtrlC> {
+    Ok(tokio::signal::windows::ctrl_c()?)
+}
+
+/// Look up the requested services by name, keeping the order given.
+fn select(services: Vec<Service>, names: &[String]) -> Result<Vec<(usize, Service)>> {
+    let unknown: Vec<&str> = names
+        .iter()
+        .filter(|name| !services.iter().any(|s| s.name == **name))
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        let available: Vec<&str> = services.iter().map(|s| s.name.as_str()).collect();
+        bail!("Unknown service(s): {}\nAvailable: {}", unknown.join(", "), available.join(", "));
+    }
+    let mut selected: Vec<(usize, Service)> = Vec::new();
+    for name in names {
+        if selected.iter().any(|(_, s)| s.name == *name) {
+            continue;
+        }
+        let index = services.iter().position(|s| s.name == *name).expect("checked above");
+        selected.push((index, services[index].clone()));
+    }
+    Ok(selected)
+}
+
+/// Run `names` without the TUI, streaming their output to stdout, and return
+/// the worst exit code among them. Services start all at once unless
+/// `sequential`; with `fail_fast` the first failure stops the rest. A first
+/// Ctrl+C stops the services gracefully, a second one kills them.
+pub async fn run(services: Vec<Service>, names: &[String], sequential: bool, fail_fast: bool) -> Result<i32> {
+    let selected = select(services, names)?;
+    let printer = Printer {
+        width: selected.iter().map(|(_, s)| s.name.chars().count()).max().unwrap_or(0),
+        // Same colors as the merged log view.
+        colors: selected.iter().map(|(i, s)| (s.name.clone(), Color::from(ui::service_color(*i)))).collect(),
+        color: std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let process_manager = Arc::new(ProcessManager::new(tx));
+    let mut queue: VecDeque<Service> = selected.into_iter().map(|(_, s)| s).collect();
+    // Services started so far, with their status kept up to date for the
+    // limit checks.
+    let mut started: Vec<Service> = Vec::new();
+    if sequential {
+        if let Some(service) = queue.pop_front() {
+            process_manager.spawn_service(service.clone()).await?;
+            started.push(service);
+        }
+    } else {
+        while let Some(service) = queue.pop_front() {
+            process_manager.spawn_service(service.clone()).await?;
+            started.push(service);
+        }
+    }
+    let mut active = started.len();
+
+    let mut watchdog = Watchdog::default();
+    let mut pending_restart: HashSet<String> = HashSet::new();
+    let mut sample = tokio::time::interval(SAMPLE_INTERVAL);
+    let mut sys = System::new();
+    let mut interrupt = interrupts()?;
+    let mut exit_codes: HashMap<String, Option<i32>> = HashMap::new();
+    let mut worst = 0;
+    let mut stopping = false;
+    while active > 0 {
+        let event = tokio::select! {
+            event = rx.recv() => event,
+            _ = sample.tick(), if !stopping => {
+                let samples;
+                (sys, samples) = metrics::sample_blocking(sys, metrics::running(&started)).await;
+                metrics::record(&mut started, samples);
+                let (lines, restarts) = watchdog.check(&mut started);
+                for line in &lines {
+                    printer.line(line);
+                }
+                for index in restarts {
+                    let name = &started[index].name;
+                    if process_manager.stop_service(name) {
+                        pending_restart.insert(name.clone());
+                    }
+                }
+                continue;
+            }
+            _ = interrupt.recv() => {
+                worst = worst.max(INTERRUPTED);
+                if stopping {
+                    printer.note("Interrupted again, killing services...");
+                    for service in &started {
+                        if let ServiceStatus::Running(pid) = service.status
+                            && pid > 0
+                        {
+                            process::kill(pid);
+                        }
+                    }
+                } else {
+                    printer.note("Interrupted, stopping services...");
+                    stopping = true;
+                    process_manager.shutdown();
+                }
+                continue;
+            }
+        };
+        let Some(event) = event else {
+            break;
+        };
+        match event {
+            Event::ServiceLog(line) => printer.line(&line),
+            Event::ServiceExit(name, code) => {
+                exit_codes.insert(name, code);
+            }
+            Event::ServiceStatus(name, status) => {
+                watchdog.status_changed(&name, &status);
+                if let Some(service) = started.iter_mut().find(|s| s.name == name) {
+                    service.status = status.clone();
+                }
+                if let ServiceStatus::Running(pid) = status {
+                    // A service that got going just as we started stopping everything.
+                    if stopping && pid > 0 {
+                        process_manager.stop_service(&name);
+                    }
+                    continue;
+                }
+                // Restarted for exceeding its memory ceiling: run it again.
+                if pending_restart.remove(&name)
+                    && !stopping
+                    && let Some(service) = started.iter().find(|s| s.name == name)
+                {
+                    process_manager.spawn_service(service.clone()).await?;
+                    continue;
+                }
+                active -= 1;
+                if status == ServiceStatus::Failed {
+                    // Killed by a signal, or never started at all.
+                    worst = worst.max(exit_codes.get(&name).copied().flatten().unwrap_or(1));
+                    if fail_fast && !stopping {
+                        printer.note(&format!("{} failed, stopping the remaining services", name));
+                        stopping = true;
+                        process_manager.shutdown();
+                    }
+                }
+                if stopping {
+                    for service in queue.drain(..) {
+                        printer.note(&format!("Skipped {}", service.name));
+                    }
+                } else if let Some(service) = queue.pop_front() {
+                    process_manager.spawn_service(service.clone()).await?;
+                    started.push(service);
+                    active += 1;
+                }
+            }
+            Event::Tick | Event::Key(_) | Event::Mouse(_) | Event::Message(_) | Event::Control(_) | Event::PortTaken(..) | Event::FilesChanged(..) | Event::ProjectChanged(_) | Event::Quit => {}
+        }
+    }
+    Ok(worst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProjectType;
+    use std::path::PathBuf;
+
+    fn services() -> Vec<Service> {
+        ["web: dev", "api: serve", "worker"]
+            .into_iter()
+            .map(|name| Service::new(name.to_string(), PathBuf::from("/p"), PathBuf::from("/p/package.json"), ProjectType::Node, String::new()))
+            .collect()
+    }
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn selection_keeps_the_order_given_and_drops_repeats() {
+        let selected = select(services(), &names(&["worker", "web: dev", "worker"])).expect("known services");
+        let picked: Vec<(usize, &str)> = selected.iter().map(|(i, s)| (*i, s.name.as_str())).collect();
+        assert_eq!(picked, [(2, "worker"), (0, "web: dev")]);
+    }
+
+    #[test]
+    fn unknown_services_are_listed_with_the_available_ones() {
+        let err = select(services(), &names(&["web: dev", "db", "cache"])).expect_err("unknown services");
+        assert_eq!(err.to_string(), "Unknown service(s): db, cache\nAvailable: web: dev, api: serve, worker");
+    }
+}
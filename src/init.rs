@@ -0,0 +1,235 @@
+use crate::cli::ConfigFormat;
+use crate::config::{self, ApiConfig, LogConfig};
+use crate::models::Service;
+use crate::tree;
+use anyhow::{bail, Result};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Services of one sidebar group, with the manifests they came from.
+struct Group<'a> {
+    name: &'a str,
+    manifests: Vec<String>,
+    services: Vec<&'a Service>,
+}
+
+fn groups<'a>(services: &'a [Service], root: &Path) -> Vec<Group<'a>> {
+    let mut groups: Vec<Group> = Vec::new();
+    for service in services {
+        let name = tree::split_name(&service.name).0;
+        let manifest = service.manifest.strip_prefix(root).unwrap_or(&service.manifest).display().to_string();
+        let index = match groups.iter().position(|g| g.name == name) {
+            Some(index) => index,
+            None => {
+                groups.push(Group { name, manifests: Vec::new(), services: Vec::new() });
+                groups.len() - 1
+            }
+        };
+        let group = &mut groups[index];
+        if !group.manifests.contains(&manifest) {
+            group.manifests.push(manifest);
+        }
+        group.services.push(service);
+    }
+    groups
+}
+
+const HEADER: &str = "\
+# DevRunner configuration, generated by `devrunner init`.
+# Everything is optional: commented-out settings show the defaults.
+# `devrunner config schema` prints a JSON Schema of this file for editors.
+";
+
+fn toml_string(s: &str) -> String {
+    toml::Value::String(s.to_string()).to_string()
+}
+
+fn yaml_string(s: &str) -> String {
+    serde_yaml::to_string(s).map(|y| y.trim_end().to_string()).unwrap_or_else(|_| format!("{:?}", s))
+}
+
+fn render_toml(groups: &[Group]) -> String {
+    let (logs, api) = (LogConfig::default(), ApiConfig::default());
+    let mut out = String::from(HEADER);
+    let _ = write!(
+        out,
+        "
+# Extra commands run from the project root, shown as \"custom: <name>\".
+# [[custom_scripts]]
+# name = \"Deploy to staging\"
+# command = \"./deploy.sh staging\"
+
+# [logs]
+# max_lines = {}
+# max_bytes = {}
+# persist = false
+# rotate_bytes = {}
+# rotate_hours = {}
+# retain_files = {}
+# retain_days = {}
+
+# [api]
+# enabled = false
+# port = {}
+",
+        logs.max_lines, logs.max_bytes, logs.rotate_bytes, logs.rotate_hours, logs.retain_files, logs.retain_days, api.port
+    );
+    for group in groups {
+        let _ = writeln!(out, "\n# {} ({})", group.name, group.manifests.join(", "));
+        for (i, service) in group.services.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            let _ = write!(
+                out,
+                "[services.{}]
+command = {}
+# env = {{ KEY = \"value\" }}
+# port = 3000
+# watch = {{ paths = [\"src/**\"] }}
+",
+                toml_string(&service.name),
+                toml_string(&service.command)
+            );
+        }
+    }
+    out
+}
+
+fn render_yaml(groups: &[Group]) -> String {
+    let (logs, api) = (LogConfig::default(), ApiConfig::default());
+    let mut out = String::from(HEADER);
+    let _ = write!(
+        out,
+        "
+# Extra commands run from the project root, shown as \"custom: <name>\".
+# custom_scripts:
+#   - name: Deploy to staging
+#     command: ./deploy.sh staging
+
+# logs:
+#   max_lines: {}
+#   max_bytes: {}
+#   persist: false
+#   rotate_bytes: {}
+#   rotate_hours: {}
+#   retain_files: {}
+#   retain_days: {}
+
+# api:
+#   enabled: false
+#   port: {}
+",
+        logs.max_lines, logs.max_bytes, logs.rotate_bytes, logs.rotate_hours, logs.retain_files, logs.retain_days, api.port
+    );
+    if !groups.is_empty() {
+        out.push_str("\nservices:\n");
+    }
+    for group in groups {
+        let _ = writeln!(out, "  # {} ({})", group.name, group.manifests.join(", "));
+        for service in &group.services {
+            let _ = write!(
+                out,
+                "  {}:
+    command: {}
+    # env:
+    #   KEY: value
+    # port: 3000
+    # watch:
+    #   paths: [\"src/**\"]
+",
+                yaml_string(&service.name),
+                yaml_string(&service.command)
+            );
+        }
+    }
+    out
+}
+
+/// JSON has no comments, so this only lists the services.
+fn render_json(groups: &[Group]) -> Result<String> {
+    let services: serde_json::Map<String, serde_json::Value> = groups
+        .iter()
+        .flat_map(|g| &g.services)
+        .map(|s| (s.name.clone(), serde_json::json!({ "command": s.command })))
+        .collect();
+    Ok(serde_json::to_string_pretty(&serde_json::json!({ "services": services }))? + "\n")
+}
+
+/// `devrunner init`: write a starter config for the discovered services.
+pub fn write_config(root: &Path, services: &[Service], format: ConfigFormat, force: bool) -> Result<()> {
+    let path = root.join(match format {
+        ConfigFormat::Json => ".devrunner.json",
+        ConfigFormat::Toml => ".devrunner.toml",
+        ConfigFormat::Yaml => ".devrunner.yaml",
+    });
+    if let Some(existing) = config::find_config(None, root) {
+        if existing != path {
+            bail!("{} already exists; remove it first to switch formats", existing.display());
+        }
+        if !force {
+            bail!("{} already exists; use --force to overwrite it", existing.display());
+        }
+    }
+    let groups = groups(services, root);
+    let content = match format {
+        ConfigFormat::Json => render_json(&groups)?,
+        ConfigFormat::Toml => render_toml(&groups),
+        ConfigFormat::Yaml => render_yaml(&groups),
+    };
+    // Whatever we write must load.
+    config::parse_config(&path, &content)?;
+    std::fs::write(&path, content)?;
+    println!("Wrote {} with {} services", path.display(), services.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProjectType;
+    use std::path::PathBuf;
+
+    fn services(root: &Path) -> Vec<Service> {
+        let service = |name: &str, manifest: &str, command: &str| {
+            let manifest = root.join(manifest);
+            let dir = manifest.parent().expect("manifest dir").to_path_buf();
+            Service::new(name.to_string(), dir, manifest, ProjectType::Node, command.to_string())
+        };
+        vec![
+            service("web: dev", "web/package.json", "npm run dev"),
+            service("web: test", "web/package.json", "npm test -- --watch=\"all\""),
+            service("api: serve", "api/Cargo.toml", "cargo run --bin 'api: server'"),
+            service("root", "package.json", "echo #not a comment"),
+        ]
+    }
+
+    fn assert_round_trips(file: &str, content: &str, services: &[Service]) {
+        let config = config::parse_config(Path::new(file), content).expect("generated config loads");
+        assert!(config.warnings.is_empty(), "{:?}", config.warnings);
+        assert_eq!(config.services.len(), services.len());
+        for service in services {
+            assert_eq!(config.services[&service.name].command.as_deref(), Some(service.command.as_str()), "{}", service.name);
+        }
+    }
+
+    #[test]
+    fn generated_configs_load_with_the_same_commands() {
+        let root = PathBuf::from("/project");
+        let services = services(&root);
+        let groups = groups(&services, &root);
+        assert_eq!(groups.iter().map(|g| g.name).collect::<Vec<_>>(), ["web", "api", "root"]);
+        assert_eq!(groups[0].manifests, ["web/package.json"]);
+
+        assert_round_trips(".devrunner.toml", &render_toml(&groups), &services);
+        assert_round_trips(".devrunner.yaml", &render_yaml(&groups), &services);
+        assert_round_trips(".devrunner.json", &render_json(&groups).expect("render json"), &services);
+    }
+
+    #[test]
+    fn configs_without_services_still_load() {
+        assert_round_trips(".devrunner.toml", &render_toml(&[]), &[]);
+        assert_round_trips(".devrunner.yaml", &render_yaml(&[]), &[]);
+        assert_round_trips(".devrunner.json", &render_json(&[]).expect("render json"), &[]);
+    }
+}
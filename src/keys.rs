@@ -0,0 +1,483 @@
+use anyhow::{bail, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Everything a key can be bound to. Key dispatch, the help overlay and the
+/// footer are all driven by this list, so they cannot drift apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    Help,
+    Cancel,
+    FindService,
+    CommandPalette,
+    Next,
+    Previous,
+    Start,
+    Stop,
+    Restart,
+    Expand,
+    Collapse,
+    SortByUsage,
+    ToggleWatch,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    ScrollTop,
+    ScrollBottom,
+    Search,
+    SearchNext,
+    SearchPrev,
+    Filter,
+    CycleStream,
+    ExpandLine,
+    PastRuns,
+    ToggleAll,
+    ToggleMute,
+    SplitPane,
+    ClosePane,
+    FocusNextPane,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Context {
+    General,
+    Services,
+    Logs,
+    Layout,
+}
+
+impl Context {
+    pub const ALL: [Context; 4] = [Context::General, Context::Services, Context::Logs, Context::Layout];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Context::General => "General",
+            Context::Services => "Services",
+            Context::Logs => "Logs",
+            Context::Layout => "Layout",
+        }
+    }
+}
+
+impl Action {
+    pub const ALL: [Action; 32] = [
+        Action::Quit,
+        Action::Help,
+        Action::Cancel,
+        Action::FindService,
+        Action::CommandPalette,
+        Action::Next,
+        Action::Previous,
+        Action::Start,
+        Action::Stop,
+        Action::Restart,
+        Action::Expand,
+        Action::Collapse,
+        Action::SortByUsage,
+        Action::ToggleWatch,
+        Action::ScrollUp,
+        Action::ScrollDown,
+        Action::PageUp,
+        Action::PageDown,
+        Action::ScrollTop,
+        Action::ScrollBottom,
+        Action::Search,
+        Action::SearchNext,
+        Action::SearchPrev,
+        Action::Filter,
+        Action::CycleStream,
+        Action::ExpandLine,
+        Action::PastRuns,
+        Action::ToggleAll,
+        Action::ToggleMute,
+        Action::SplitPane,
+        Action::ClosePane,
+        Action::FocusNextPane,
+    ];
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Help => "Toggle this help",
+            Action::Cancel => "Clear search / close archive",
+            Action::FindService => "Find service by name, path or type",
+            Action::CommandPalette => "Command palette",
+            Action::Next => "Select next service",
+            Action::Previous => "Select previous service",
+            Action::Start => "Start service or group",
+            Action::Stop => "Stop service or group",
+            Action::Restart => "Restart service or group",
+            Action::Expand => "Expand group",
+            Action::Collapse => "Collapse group / go to parent",
+            Action::SortByUsage => "Sort by CPU / memory / tree",
+            Action::ToggleWatch => "Toggle restart on file changes",
+            Action::ScrollUp => "Scroll up one line",
+            Action::ScrollDown => "Scroll down one line",
+            Action::PageUp => "Scroll up one page",
+            Action::PageDown => "Scroll down one page",
+            Action::ScrollTop => "Jump to oldest line",
+            Action::ScrollBottom => "Jump to newest line and follow",
+            Action::Search => "Search logs",
+            Action::SearchNext => "Next match",
+            Action::SearchPrev => "Previous match",
+            Action::Filter => "Filter logs",
+            Action::CycleStream => "Cycle stream filter (all/stdout/stderr)",
+            Action::ExpandLine => "Expand line (pretty-print record)",
+            Action::PastRuns => "Browse persisted past runs",
+            Action::ToggleAll => "Toggle merged all-services view",
+            Action::ToggleMute => "Mute service in merged view",
+            Action::SplitPane => "Split: pin service to a new pane",
+            Action::ClosePane => "Close focused pane",
+            Action::FocusNextPane => "Focus next pane",
+        }
+    }
+
+    /// The name used in the config's `keybindings` table.
+    pub fn config_name(self) -> String {
+        serde_json::to_value(self).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default()
+    }
+
+    /// Short label used in the footer.
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Help => "Help",
+            Action::Start => "Start",
+            Action::Stop => "Stop",
+            Action::Restart => "Restart",
+            Action::Search => "Search",
+            Action::Filter => "Filter",
+            Action::SplitPane => "Split",
+            Action::ToggleWatch => "Watch",
+            _ => self.description(),
+        }
+    }
+
+    pub fn context(self) -> Context {
+        match self {
+            Action::Quit | Action::Help | Action::Cancel | Action::FindService | Action::CommandPalette => {
+                Context::General
+            }
+            Action::Next
+            | Action::Previous
+            | Action::Start
+            | Action::Stop
+            | Action::Restart
+            | Action::Expand
+            | Action::Collapse
+            | Action::SortByUsage
+            | Action::ToggleWatch => Context::Services,
+            Action::ToggleAll
+            | Action::ToggleMute
+            | Action::SplitPane
+            | Action::ClosePane
+            | Action::FocusNextPane => Context::Layout,
+            _ => Context::Logs,
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::Help => &["?", "h"],
+            Action::Cancel => &["esc"],
+            Action::FindService => &["ctrl+p"],
+            Action::CommandPalette => &[":"],
+            Action::Next => &["down", "j"],
+            Action::Previous => &["up", "k"],
+            Action::Start => &["enter", "s"],
+            Action::Stop => &["x"],
+            Action::Restart => &["r"],
+            Action::Expand => &["right", "l"],
+            Action::Collapse => &["left"],
+            Action::SortByUsage => &["u"],
+            Action::ToggleWatch => &["w"],
+            Action::ScrollUp => &["shift+up"],
+            Action::ScrollDown => &["shift+down"],
+            Action::PageUp => &["pgup"],
+            Action::PageDown => &["pgdn"],
+            Action::ScrollTop => &["home"],
+            Action::ScrollBottom => &["end"],
+            Action::Search => &["/"],
+            Action::SearchNext => &["n"],
+            Action::SearchPrev => &["N"],
+            Action::Filter => &["f"],
+            Action::CycleStream => &["t"],
+            Action::ExpandLine => &["v"],
+            Action::PastRuns => &["o"],
+            Action::ToggleAll => &["a"],
+            Action::ToggleMute => &["m"],
+            Action::SplitPane => &["p"],
+            Action::ClosePane => &["X"],
+            Action::FocusNextPane => &["tab"],
+        }
+    }
+}
+
+/// A key plus modifiers, e.g. `ctrl+p`. For character keys shift is folded
+/// into the character itself (`N` rather than `shift+n`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    fn normalize(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Self { code, modifiers }
+    }
+
+    pub fn from_event(key: &KeyEvent) -> Self {
+        Self::normalize(key.code, key.modifiers)
+    }
+
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = spec.split('+').collect();
+        // A bare "+" (or "ctrl++") binds the plus key itself.
+        if spec.ends_with("++") || spec == "+" {
+            parts.retain(|p| !p.is_empty());
+            parts.push("+");
+        }
+        let Some((key, mods)) = parts.split_last() else {
+            bail!("empty key binding");
+        };
+        for m in mods {
+            modifiers |= match m.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                other => bail!("unknown modifier '{}' in key binding '{}'", other, spec),
+            };
+        }
+        let code = match key.to_ascii_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pgup" | "pageup" => KeyCode::PageUp,
+            "pgdn" | "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "space" => KeyCode::Char(' '),
+            lower if lower.len() > 1 && lower.starts_with('f') && lower[1..].parse::<u8>().is_ok() => {
+                KeyCode::F(lower[1..].parse()?)
+            }
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                        KeyCode::Char(c.to_ascii_uppercase())
+                    }
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => bail!("unknown key '{}' in key binding '{}'", key, spec),
+                }
+            }
+        };
+        Ok(Self::normalize(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// One key or several keys bound to an action in the config file.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum KeySpec {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeySpec {
+    fn keys(&self) -> Vec<&str> {
+        match self {
+            KeySpec::One(key) => vec![key.as_str()],
+            KeySpec::Many(keys) => keys.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(KeyBinding, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .iter()
+            .flat_map(|action| {
+                action
+                    .default_keys()
+                    .iter()
+                    .map(move |spec| (KeyBinding::parse(spec).expect("valid default key binding"), *action))
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// Default bindings with the config's `keybindings` applied: an
+    /// overridden action loses its default keys, and a key taken by an
+    /// override is removed from whatever action had it before. Giving the
+    /// same key to two actions is an error.
+    pub fn with_overrides(overrides: &HashMap<Action, KeySpec>) -> Result<Self> {
+        let mut keymap = Self::default();
+        let mut claimed: Vec<(KeyBinding, Action)> = Vec::new();
+        // In `ALL` order, so the result doesn't depend on hash order.
+        for action in Action::ALL.iter().filter(|a| overrides.contains_key(a)) {
+            let keys = overrides[action]
+                .keys()
+                .into_iter()
+                .map(KeyBinding::parse)
+                .collect::<Result<Vec<_>>>()?;
+            for key in &keys {
+                if let Some((_, other)) = claimed.iter().find(|(k, a)| k == key && a != action) {
+                    bail!("key '{}' is bound to both {} and {}", key, other.config_name(), action.config_name());
+                }
+                claimed.push((*key, *action));
+            }
+            keymap
+                .bindings
+                .retain(|(key, bound)| bound != action && !keys.contains(key));
+            keymap.bindings.extend(keys.into_iter().map(|key| (key, *action)));
+        }
+        Ok(keymap)
+    }
+
+    pub fn action_for(&self, key: &KeyEvent) -> Option<Action> {
+        let binding = KeyBinding::from_event(key);
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == binding)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn keys_for(&self, action: Action) -> Vec<KeyBinding> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    /// Keys for an action joined for display, e.g. `Enter/s`.
+    pub fn describe(&self, action: Action) -> String {
+        let keys: Vec<String> = self.keys_for(action).iter().map(ToString::to_string).collect();
+        if keys.is_empty() {
+            "unbound".to_string()
+        } else {
+            keys.join("/")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn key(spec: &str) -> KeyBinding {
+        KeyBinding::parse(spec).expect("valid key binding")
+    }
+
+    fn event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn parses_modifiers_and_named_keys() {
+        assert_eq!(key("Ctrl+P"), KeyBinding::normalize(KeyCode::Char('P'), KeyModifiers::CONTROL));
+        assert_eq!(key("alt+enter"), KeyBinding::normalize(KeyCode::Enter, KeyModifiers::ALT));
+        assert_eq!(key("f5"), KeyBinding::normalize(KeyCode::F(5), KeyModifiers::NONE));
+        assert_eq!(key("space").to_string(), "Space");
+        assert_eq!(key("ctrl++").to_string(), "Ctrl++");
+        assert_eq!(key("+").to_string(), "+");
+        // A lone `f` is the letter, not a function key.
+        assert_eq!(key("f").to_string(), "f");
+        for bad in ["", "hyper+x", "ctrl+nope"] {
+            assert!(KeyBinding::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn shift_is_folded_into_characters() {
+        assert_eq!(key("shift+n"), key("N"));
+        assert_eq!(KeyBinding::from_event(&event(KeyCode::Char('N'), KeyModifiers::SHIFT)), key("N"));
+        assert_ne!(key("shift+up"), key("up"));
+    }
+
+    #[test]
+    fn every_action_is_listed_once_with_distinct_default_keys() {
+        let actions: HashSet<Action> = Action::ALL.into_iter().collect();
+        assert_eq!(actions.len(), Action::ALL.len());
+        let keymap = Keymap::default();
+        let keys: HashSet<KeyBinding> = keymap.bindings.iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys.len(), keymap.bindings.len(), "a default key is bound twice");
+        assert_eq!(Action::FocusNextPane.config_name(), "focus_next_pane");
+    }
+
+    #[test]
+    fn overrides_replace_defaults_and_take_keys_over() {
+        let overrides = HashMap::from([
+            (Action::Quit, KeySpec::One("ctrl+q".to_string())),
+            (Action::Restart, KeySpec::Many(vec!["R".to_string(), "s".to_string()])),
+        ]);
+        let keymap = Keymap::with_overrides(&overrides).expect("valid overrides");
+        assert_eq!(keymap.describe(Action::Quit), "Ctrl+q");
+        assert_eq!(keymap.action_for(&event(KeyCode::Char('q'), KeyModifiers::NONE)), None);
+        // `s` moved from start to restart; start keeps enter.
+        assert_eq!(keymap.describe(Action::Restart), "R/s");
+        assert_eq!(keymap.describe(Action::Start), "Enter");
+        assert_eq!(keymap.action_for(&event(KeyCode::Char('s'), KeyModifiers::NONE)), Some(Action::Restart));
+    }
+
+    #[test]
+    fn a_key_bound_to_two_actions_is_rejected_in_a_stable_order() {
+        let overrides = HashMap::from([
+            (Action::Help, KeySpec::One("z".to_string())),
+            (Action::Quit, KeySpec::One("z".to_string())),
+        ]);
+        let err = Keymap::with_overrides(&overrides).expect_err("conflicting overrides");
+        assert_eq!(err.to_string(), "key 'z' is bound to both quit and help");
+        let bad = HashMap::from([(Action::Quit, KeySpec::One("ctrl+".to_string()))]);
+        assert!(Keymap::with_overrides(&bad).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+use crate::config::LimitsConfig;
+use crate::metrics::Usage;
+use crate::models::{LogLevel, LogLine, Service, ServiceStatus};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// Bytes in the MiB used by the `limits` config section.
+pub const MIB: u64 = 1024 * 1024;
+
+/// Kernel-side limits applied to a spawned service.
+#[derive(Debug, Default)]
+pub struct Enforcement {
+    /// Cgroup created for the run, removed again once it has exited.
+    pub cgroup: Option<PathBuf>,
+    /// Human-readable summary for the service's log, e.g.
+    /// `memory 512 MiB (cgroup), CPU time 600s (rlimit)`.
+    pub summary: Vec<String>,
+}
+
+impl Enforcement {
+    pub fn cleanup(&self) {
+        if let Some(dir) = &self.cgroup {
+            // Only succeeds once every process has left; a leftover empty
+            // cgroup is harmless.
+            let _ = std::fs::remove_dir(dir);
+        }
+    }
+}
+
+/// Find a cgroup v2 directory we may create a child with the memory
+/// controller in: a sibling of DevRunner's own cgroup, since a cgroup
+/// holding processes can't hand controllers down to children.
+#[cfg(target_os = "linux")]
+fn create_cgroup(service: &str, memory_bytes: u64) -> Option<PathBuf> {
+    use std::fs;
+    use std::path::Path;
+
+    let own = fs::read_to_string("/proc/self/cgroup").ok()?;
+    let rel = own.lines().find_map(|line| line.strip_prefix("0::"))?;
+    let own_dir = Path::new("/sys/fs/cgroup").join(rel.trim_start_matches('/'));
+    let parent = own_dir.parent()?;
+    let controllers = fs::read_to_string(parent.join("cgroup.subtree_control")).ok()?;
+    if !controllers.split_whitespace().any(|c| c == "memory") {
+        return None;
+    }
+    let dir = parent.join(format!(
+        "devrunner-{}-{}",
+        crate::archive::service_dir_name(service),
+        std::process::id()
+    ));
+    fs::create_dir_all(&dir).ok()?;
+    if fs::write(dir.join("memory.max"), memory_bytes.to_string()).is_err() {
+        let _ = fs::remove_dir(&dir);
+        return None;
+    }
+    Some(dir)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn create_cgroup(_service: &str, _memory_bytes: u64) -> Option<PathBuf> {
+    None
+}
+
+/// Arrange for the hard limits in `limits` to apply to the child and
+/// everything it starts.
+#[cfg(unix)]
+pub fn apply(command: &mut Command, service: &str, limits: &LimitsConfig) -> Enforcement {
+    use std::ffi::CString;
+
+    let mut enforcement = Enforcement::default();
+    // With `restart_on_memory` the ceiling is watched by DevRunner instead.
+    let memory = limits.memory_mb.filter(|_| !limits.restart_on_memory).map(|mb| mb * MIB);
+    let mut rlimit_as = None;
+    if let Some(bytes) = memory {
+        enforcement.cgroup = create_cgroup(service, bytes);
+        let how = if enforcement.cgroup.is_some() {
+            "cgroup"
+        } else if limits.memory_rlimit {
+            rlimit_as = Some(bytes);
+            "rlimit"
+        } else {
+            "not enforced: no cgroup available, see memory_rlimit"
+        };
+        enforcement.summary.push(format!("memory {} MiB ({})", bytes / MIB, how));
+    }
+    let cpu = limits.cpu_seconds;
+    if let Some(seconds) = cpu {
+        enforcement.summary.push(format!("CPU time {}s (rlimit)", seconds));
+    }
+    let procs = enforcement
+        .cgroup
+        .as_ref()
+        .and_then(|dir| CString::new(dir.join("cgroup.procs").into_os_string().into_encoded_bytes()).ok());
+    if procs.is_none() && rlimit_as.is_none() && cpu.is_none() {
+        return enforcement;
+    }
+
+    // Runs in the forked child before exec, so only async-signal-safe calls.
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(procs) = &procs {
+                // Writing "0" moves the writing process itself.
+                let fd = libc::open(procs.as_ptr(), libc::O_WRONLY);
+                if fd >= 0 {
+                    libc::write(fd, b"0".as_ptr().cast(), 1);
+                    libc::close(fd);
+                }
+            }
+            if let Some(bytes) = rlimit_as {
+                let limit = libc::rlimit { rlim_cur: bytes as libc::rlim_t, rlim_max: bytes as libc::rlim_t };
+                libc::setrlimit(libc::RLIMIT_AS, &limit);
+            }
+            if let Some(seconds) = cpu {
+                let limit = libc::rlimit { rlim_cur: seconds as libc::rlim_t, rlim_max: seconds as libc::rlim_t };
+                libc::setrlimit(libc::RLIMIT_CPU, &limit);
+            }
+            Ok(())
+        });
+    }
+    enforcement
+}
+
+#[cfg(not(unix))]
+pub fn apply(_command: &mut Command, _service: &str, limits: &LimitsConfig) -> Enforcement {
+    let mut enforcement = Enforcement::default();
+    if limits.memory_mb.is_some() || limits.cpu_seconds.is_some() {
+        enforcement.summary.push("not enforced on this platform".to_string());
+    }
+    enforcement
+}
+
+fn format_mib(bytes: u64) -> String {
+    format!("{} MiB", bytes / MIB)
+}
+
+/// The alert thresholds in `limits` that `usage` is above, described for
+/// the service's log.
+pub fn alerts(limits: &LimitsConfig, usage: &Usage) -> Vec<String> {
+    let mut alerts = Vec::new();
+    if let Some(mb) = limits.alert_memory_mb
+        && usage.rss > mb * MIB
+    {
+        alerts.push(format!("memory {} above alert threshold of {} MiB", format_mib(usage.rss), mb));
+    }
+    if let Some(percent) = limits.alert_cpu_percent
+        && usage.cpu > percent
+    {
+        alerts.push(format!("CPU {:.0}% above alert threshold of {:.0}%", usage.cpu, percent));
+    }
+    alerts
+}
+
+/// Checks sampled usage against the services' limits. Whatever runs the
+/// services (the TUI's own process manager, the daemon or a headless run)
+/// owns one, so limits hold whether or not a TUI is watching.
+#[derive(Debug, Default)]
+pub struct Watchdog {
+    /// Pid of the run each service was restarted from for exceeding its
+    /// memory ceiling, so one overshoot leads to one restart.
+    restarted: HashMap<String, u32>,
+}
+
+impl Watchdog {
+    /// Log services crossing an alert threshold, and pick those over their
+    /// memory ceiling for a restart when configured to. Returns the lines
+    /// to log and the indices of the services to restart.
+    pub fn check(&mut self, services: &mut [Service]) -> (Vec<LogLine>, Vec<usize>) {
+        let mut lines = Vec::new();
+        let mut restarts = Vec::new();
+        for (index, service) in services.iter_mut().enumerate() {
+            let Some(usage) = service.metrics.current else {
+                continue;
+            };
+            let limits = &service.limits;
+            let alerts = alerts(limits, &usage);
+            let alerting = !alerts.is_empty();
+            if alerting && !service.metrics.alerting {
+                lines.push(LogLine::system(&service.name, LogLevel::Warn, format!("Resource alert: {}", alerts.join(", "))));
+            }
+            service.metrics.alerting = alerting;
+
+            if limits.restart_on_memory
+                && let Some(mb) = limits.memory_mb
+                && usage.rss > mb * MIB
+                && let ServiceStatus::Running(pid) = service.status
+                && !self.restarted.contains_key(&service.name)
+            {
+                self.restarted.insert(service.name.clone(), pid);
+                lines.push(LogLine::system(
+                    &service.name,
+                    LogLevel::Error,
+                    format!("Memory {} exceeds the {} MiB ceiling, restarting", format_mib(usage.rss), mb),
+                ));
+                restarts.push(index);
+            }
+        }
+        (lines, restarts)
+    }
+
+    /// Track a service's new status: once it runs again under another pid
+    /// it may be restarted again.
+    pub fn status_changed(&mut self, name: &str, status: &ServiceStatus) {
+        if let ServiceStatus::Running(pid) = status
+            && self.restarted.get(name).is_some_and(|old| old != pid)
+        {
+            self.restarted.remove(name);
+        }
+    }
+
+    pub fn forget(&mut self, name: &str) {
+        self.restarted.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_config;
+    use crate::models::ProjectType;
+    use std::path::Path;
+
+    fn service(limits: LimitsConfig, pid: u32, rss_mb: u64) -> Service {
+        let mut service = Service::new("web".to_string(), "/repo".into(), "/repo/package.json".into(), ProjectType::Node, String::new());
+        service.limits = limits;
+        service.status = ServiceStatus::Running(pid);
+        service.metrics.record(Usage { rss: rss_mb * MIB, cpu: 50.0, ..Usage::default() });
+        service
+    }
+
+    #[test]
+    fn limits_are_read_per_service() {
+        let config = parse_config(
+            Path::new(".devrunner.json"),
+            r#"{"services": {"web": {"limits": {"memory_mb": 512, "alert_cpu_percent": 90.5, "restart_on_memory": true}}}}"#,
+        )
+        .expect("valid config");
+        let limits = config.limits("web");
+        assert_eq!(limits.memory_mb, Some(512));
+        assert_eq!(limits.alert_cpu_percent, Some(90.5));
+        assert!(limits.restart_on_memory);
+        assert!(!limits.memory_rlimit);
+        assert_eq!(config.limits("api"), LimitsConfig::default());
+        assert!(parse_config(Path::new(".devrunner.json"), r#"{"services": {"web": {"limits": {"memory_mb": -1}}}}"#).is_err());
+    }
+
+    #[test]
+    fn alerts_name_each_threshold_crossed() {
+        let limits = LimitsConfig { alert_memory_mb: Some(100), alert_cpu_percent: Some(40.0), ..LimitsConfig::default() };
+        let usage = Usage { rss: 150 * MIB, cpu: 45.0, ..Usage::default() };
+        assert_eq!(
+            alerts(&limits, &usage),
+            ["memory 150 MiB above alert threshold of 100 MiB", "CPU 45% above alert threshold of 40%"]
+        );
+        assert!(alerts(&limits, &Usage { rss: 100 * MIB, cpu: 40.0, ..Usage::default() }).is_empty());
+    }
+
+    #[test]
+    fn alerts_are_logged_once_per_crossing() {
+        let limits = LimitsConfig { alert_memory_mb: Some(100), ..LimitsConfig::default() };
+        let mut services = [service(limits, 10, 200)];
+        let mut watchdog = Watchdog::default();
+        let (lines, restarts) = watchdog.check(&mut services);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].level, Some(LogLevel::Warn));
+        assert!(restarts.is_empty());
+        assert!(services[0].metrics.alerting);
+        assert!(watchdog.check(&mut services).0.is_empty());
+        services[0].metrics.record(Usage { rss: 50 * MIB, ..Usage::default() });
+        watchdog.check(&mut services);
+        assert!(!services[0].metrics.alerting);
+    }
+
+    #[test]
+    fn memory_ceiling_restarts_each_run_once() {
+        let limits = LimitsConfig { memory_mb: Some(100), restart_on_memory: true, ..LimitsConfig::default() };
+        let mut services = [service(limits.clone(), 10, 200)];
+        let mut watchdog = Watchdog::default();
+        assert_eq!(watchdog.check(&mut services).1, [0]);
+        // Still the same run while it shuts down.
+        assert!(watchdog.check(&mut services).1.is_empty());
+        watchdog.status_changed("web", &ServiceStatus::Running(10));
+        assert!(watchdog.check(&mut services).1.is_empty());
+        // The new run may be restarted again.
+        services[0].status = ServiceStatus::Running(11);
+        watchdog.status_changed("web", &services[0].status);
+        assert_eq!(watchdog.check(&mut services).1, [0]);
+
+        let kernel_enforced = LimitsConfig { restart_on_memory: false, ..limits };
+        assert!(Watchdog::default().check(&mut [service(kernel_enforced, 10, 200)]).1.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restart_on_memory_leaves_memory_to_the_watchdog() {
+        let limits = LimitsConfig { memory_mb: Some(100), cpu_seconds: Some(600), restart_on_memory: true, ..LimitsConfig::default() };
+        let enforcement = apply(&mut Command::new("true"), "web", &limits);
+        assert!(enforcement.cgroup.is_none());
+        assert_eq!(enforcement.summary, ["CPU time 600s (rlimit)"]);
+    }
+}
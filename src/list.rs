@@ -0,0 +1,122 @@
+use crate::cli::ListFormat;
+use crate::models::Service;
+use crate::tree;
+use anyhow::Result;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// A discovered service as printed by `devrunner list`.
+#[derive(Serialize)]
+struct Entry<'a> {
+    #[serde(flatten)]
+    service: &'a Service,
+    /// Package the service belongs to, as grouped in the sidebar.
+    group: &'a str,
+}
+
+fn relative(path: &Path, root: &Path) -> String {
+    let rel = path.strip_prefix(root).unwrap_or(path);
+    if rel.as_os_str().is_empty() { ".".to_string() } else { rel.display().to_string() }
+}
+
+pub fn print_services(services: &[Service], root: &Path, format: ListFormat) -> Result<()> {
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    match write_services(&mut out, services, root, format).and_then(|_| out.flush()) {
+        // Piped into `head` or similar.
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(Into::into),
+    }
+}
+
+fn write_services(out: &mut impl Write, services: &[Service], root: &Path, format: ListFormat) -> io::Result<()> {
+    let entries: Vec<Entry> = services
+        .iter()
+        .map(|service| Entry { service, group: tree::split_name(&service.name).0 })
+        .collect();
+    match format {
+        ListFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(&entries)?)?,
+        ListFormat::Ndjson => {
+            for entry in &entries {
+                writeln!(out, "{}", serde_json::to_string(entry)?)?;
+            }
+        }
+        ListFormat::Table => {
+            let header = ["NAME", "TYPE", "GROUP", "COMMAND", "CWD", "MANIFEST"].map(str::to_string);
+            let rows: Vec<[String; 6]> = entries
+                .iter()
+                .map(|e| {
+                    [
+                        e.service.name.clone(),
+                        format!("{:?}", e.service.project_type),
+                        e.group.to_string(),
+                        e.service.command.clone(),
+                        relative(&e.service.path, root),
+                        relative(&e.service.manifest, root),
+                    ]
+                })
+                .collect();
+            let mut widths = [0; 6];
+            for row in std::iter::once(&header).chain(&rows) {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+            for row in std::iter::once(&header).chain(&rows) {
+                let cells: Vec<String> = row.iter().zip(widths).map(|(cell, width)| format!("{:<width$}", cell)).collect();
+                writeln!(out, "{}", cells.join("  ").trim_end())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProjectType;
+
+    fn services() -> Vec<Service> {
+        let web = Path::new("/repo/apps/web");
+        vec![
+            Service::new("web: dev".to_string(), web.to_path_buf(), web.join("package.json"), ProjectType::Node, "npm run dev".to_string()),
+            Service::new("tool: run".to_string(), "/repo".into(), "/repo/Cargo.toml".into(), ProjectType::Rust, "cargo run".to_string()),
+        ]
+    }
+
+    fn output(format: ListFormat) -> String {
+        let mut out = Vec::new();
+        write_services(&mut out, &services(), Path::new("/repo"), format).expect("written");
+        String::from_utf8(out).expect("utf-8")
+    }
+
+    #[test]
+    fn table_columns_are_aligned() {
+        assert_eq!(
+            output(ListFormat::Table),
+            "NAME       TYPE  GROUP  COMMAND      CWD       MANIFEST\n\
+             web: dev   Node  web    npm run dev  apps/web  apps/web/package.json\n\
+             tool: run  Rust  tool   cargo run    .         Cargo.toml\n"
+        );
+    }
+
+    #[test]
+    fn ndjson_has_one_object_per_line() {
+        let lines: Vec<serde_json::Value> =
+            output(ListFormat::Ndjson).lines().map(|l| serde_json::from_str(l).expect("json line")).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["name"], "web: dev");
+        assert_eq!(lines[0]["group"], "web");
+        assert_eq!(lines[0]["cwd"], "/repo/apps/web");
+        assert_eq!(lines[1]["project_type"], "Rust");
+        assert_eq!(lines[1]["command"], "cargo run");
+    }
+
+    #[test]
+    fn json_is_a_single_array() {
+        let value: serde_json::Value = serde_json::from_str(&output(ListFormat::Json)).expect("json");
+        let entries = value.as_array().expect("array");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1]["manifest"], "/repo/Cargo.toml");
+    }
+}
@@ -0,0 +1,343 @@
+use crate::models::LogLevel;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+/// Map a level name as written by common loggers to a `LogLevel`.
+pub fn level_from_name(name: &str) -> Option<LogLevel> {
+    match name.to_ascii_lowercase().as_str() {
+        "trace" | "trc" => Some(LogLevel::Trace),
+        "debug" | "dbg" => Some(LogLevel::Debug),
+        "info" | "inf" | "information" | "notice" => Some(LogLevel::Info),
+        "warn" | "wrn" | "warning" => Some(LogLevel::Warn),
+        "error" | "err" | "fatal" | "critical" | "crit" | "panic" => Some(LogLevel::Error),
+        _ => None,
+    }
+}
+
+/// Numeric levels used by pino and bunyan.
+fn level_from_number(n: i64) -> LogLevel {
+    match n {
+        ..=10 => LogLevel::Trace,
+        11..=20 => LogLevel::Debug,
+        21..=30 => LogLevel::Info,
+        31..=40 => LogLevel::Warn,
+        _ => LogLevel::Error,
+    }
+}
+
+const LEVEL_KEYS: [&str; 4] = ["level", "lvl", "severity", "log.level"];
+
+/// Keyword heuristics: a `level=warn` token in a line that isn't quite
+/// logfmt (`level=warn starting server`), an upper-case level word among the
+/// first few tokens (`2024-01-01 WARN ...`, `[ERROR] ...`, `npm ERR! ...`) or
+/// a compiler-style `error:` / `warning[E123]:` prefix.
+fn detect_keyword(raw: &str) -> Option<LogLevel> {
+    let trimmed = raw.trim_start();
+    let tagged = trimmed.split_whitespace().find_map(|token| {
+        let (key, value) = token.split_once('=')?;
+        LEVEL_KEYS.contains(&key).then(|| level_from_name(value.trim_matches(|c| c == '"' || c == '\'')))?
+    });
+    if tagged.is_some() {
+        return tagged;
+    }
+    for (prefix, level) in [
+        ("error", LogLevel::Error),
+        ("warning", LogLevel::Warn),
+        ("warn", LogLevel::Warn),
+        ("info", LogLevel::Info),
+        ("debug", LogLevel::Debug),
+    ] {
+        if let Some(rest) = trimmed.strip_prefix(prefix)
+            && (rest.starts_with(':') || rest.starts_with('['))
+        {
+            return Some(level);
+        }
+    }
+
+    trimmed.split_whitespace().take(6).find_map(|token| {
+        let word = token.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+        if word.len() >= 3 && word.chars().all(|c| c.is_ascii_uppercase()) {
+            level_from_name(word)
+        } else {
+            None
+        }
+    })
+}
+
+/// Best-effort detection of the severity of a line of program output,
+/// preferring the level field of a structured record when there is one.
+pub fn detect_level(raw: &str, record: Option<&Record>) -> Option<LogLevel> {
+    record.and_then(Record::level).or_else(|| detect_keyword(raw))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RecordFormat {
+    Json,
+    Logfmt,
+}
+
+/// A JSON or logfmt log line broken into its well-known parts plus the
+/// remaining fields, flattened to dotted keys (`req.id`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub format: RecordFormat,
+    pub time: Option<String>,
+    pub level: Option<String>,
+    pub msg: Option<String>,
+    pub fields: Vec<(String, String)>,
+}
+
+const TIME_KEYS: [&str; 4] = ["time", "timestamp", "ts", "@timestamp"];
+const MSG_KEYS: [&str; 3] = ["msg", "message", "@message"];
+
+impl Record {
+    fn from_pairs(format: RecordFormat, pairs: Vec<(String, String)>) -> Self {
+        let mut record = Record {
+            format,
+            time: None,
+            level: None,
+            msg: None,
+            fields: Vec::new(),
+        };
+        for (key, value) in pairs {
+            if record.time.is_none() && TIME_KEYS.contains(&key.as_str()) {
+                record.time = Some(value);
+            } else if record.level.is_none() && LEVEL_KEYS.contains(&key.as_str()) {
+                record.level = Some(value);
+            } else if record.msg.is_none() && MSG_KEYS.contains(&key.as_str()) {
+                record.msg = Some(value);
+            } else {
+                record.fields.push((key, value));
+            }
+        }
+        record
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        match key {
+            "time" => self.time.as_deref(),
+            "level" => self.level.as_deref(),
+            "msg" => self.msg.as_deref(),
+            _ => self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str()),
+        }
+    }
+
+    pub fn level(&self) -> Option<LogLevel> {
+        let level = self.level.as_deref()?;
+        level_from_name(level).or_else(|| level.parse().ok().map(level_from_number))
+    }
+
+    /// The record's own time, from an RFC 3339 or `YYYY-MM-DD HH:MM:SS`
+    /// string or a Unix timestamp in seconds or milliseconds.
+    pub fn timestamp(&self) -> Option<DateTime<Local>> {
+        let time = self.time.as_deref()?.trim();
+        if let Ok(parsed) = DateTime::parse_from_rfc3339(time) {
+            return Some(parsed.with_timezone(&Local));
+        }
+        if let Ok(naive) = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f") {
+            return Local.from_local_datetime(&naive).earliest();
+        }
+        let number: f64 = time.parse().ok()?;
+        let millis = if number.abs() >= 1e11 { number } else { number * 1000.0 };
+        DateTime::from_timestamp_millis(millis as i64).map(|t| t.with_timezone(&Local))
+    }
+
+    /// Compact single-line rendering: `msg key=value ...`, led by the
+    /// record's time when it is in a format `timestamp` can't read.
+    pub fn summary(&self) -> String {
+        let mut out = match &self.time {
+            Some(time) if self.timestamp().is_none() => time.clone(),
+            _ => String::new(),
+        };
+        if let Some(msg) = &self.msg {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            out.push_str(msg);
+        }
+        for (key, value) in &self.fields {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            if value.contains(char::is_whitespace) {
+                out.push_str(&format!("{}={:?}", key, value));
+            } else {
+                out.push_str(&format!("{}={}", key, value));
+            }
+        }
+        out
+    }
+}
+
+fn flatten(prefix: &str, value: &serde_json::Value, out: &mut Vec<(String, String)>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, child) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(&path, child, out);
+            }
+        }
+        serde_json::Value::String(s) => out.push((prefix.to_string(), s.clone())),
+        other => out.push((prefix.to_string(), other.to_string())),
+    }
+}
+
+/// Split a logfmt line into key/value pairs, honouring double quotes.
+fn logfmt_pairs(raw: &str) -> Option<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+    let mut chars = raw.trim().chars().peekable();
+    while chars.peek().is_some() {
+        let mut key = String::new();
+        loop {
+            match chars.next() {
+                Some('=') => break,
+                Some(c) if c.is_whitespace() => return None,
+                Some(c) => key.push(c),
+                None => return None,
+            }
+        }
+        if key.is_empty() {
+            return None;
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            let mut escaped = false;
+            for c in chars.by_ref() {
+                match c {
+                    '\\' if !escaped => escaped = true,
+                    '"' if !escaped => break,
+                    _ => {
+                        value.push(c);
+                        escaped = false;
+                    }
+                }
+            }
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+        } else {
+            value = chars.by_ref().take_while(|c| !c.is_whitespace()).collect();
+        }
+        pairs.push((key, value));
+    }
+    Some(pairs)
+}
+
+/// Parse a JSON object or logfmt record; plain text yields `None`.
+pub fn parse_record(raw: &str) -> Option<Record> {
+    let trimmed = raw.trim();
+    if trimmed.starts_with('{') {
+        let value: serde_json::Value = serde_json::from_str(trimmed).ok()?;
+        if !value.is_object() {
+            return None;
+        }
+        let mut pairs = Vec::new();
+        flatten("", &value, &mut pairs);
+        return Some(Record::from_pairs(RecordFormat::Json, pairs));
+    }
+    let pairs = logfmt_pairs(trimmed)?;
+    // Require a couple of pairs so prose containing a stray `=` isn't mistaken for logfmt.
+    if pairs.len() < 2 {
+        return None;
+    }
+    Some(Record::from_pairs(RecordFormat::Logfmt, pairs))
+}
+
+/// Multi-line rendering of a log line for the detail popup.
+pub fn pretty(raw: &str, record: Option<&Record>) -> Vec<String> {
+    let trimmed = raw.trim();
+    match record.map(|r| r.format) {
+        Some(RecordFormat::Json) => serde_json::from_str::<serde_json::Value>(trimmed)
+            .and_then(|v| serde_json::to_string_pretty(&v))
+            .map(|s| s.lines().map(str::to_string).collect())
+            .unwrap_or_else(|_| vec![raw.to_string()]),
+        Some(RecordFormat::Logfmt) => logfmt_pairs(trimmed)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(k, v)| format!("{}: {}", k, v))
+            .collect(),
+        None => vec![raw.to_string()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(raw: &str) -> Option<LogLevel> {
+        detect_level(raw, parse_record(raw).as_ref())
+    }
+
+    #[test]
+    fn json_level_names_and_numbers() {
+        assert_eq!(level(r#"{"level":"warn","msg":"slow"}"#), Some(LogLevel::Warn));
+        assert_eq!(level(r#"{"severity":"ERROR","message":"boom"}"#), Some(LogLevel::Error));
+        // pino / bunyan
+        assert_eq!(level(r#"{"level":30,"msg":"up"}"#), Some(LogLevel::Info));
+        assert_eq!(level(r#"{"level":50,"msg":"down"}"#), Some(LogLevel::Error));
+        assert_eq!(level(r#"{"log":{"level":"debug"}}"#), Some(LogLevel::Debug));
+    }
+
+    #[test]
+    fn logfmt_level() {
+        assert_eq!(level(r#"ts=2024-01-01 level=error msg="bad thing""#), Some(LogLevel::Error));
+        assert_eq!(level("lvl=dbg msg=x"), Some(LogLevel::Debug));
+    }
+
+    #[test]
+    fn level_token_outside_logfmt() {
+        assert!(parse_record("level=warn starting server").is_none());
+        assert_eq!(level("level=warn starting server"), Some(LogLevel::Warn));
+        assert_eq!(level("12:00:01 lvl=\"error\" connection reset"), Some(LogLevel::Error));
+        assert_eq!(level("level=verbose something"), None);
+    }
+
+    #[test]
+    fn keywords() {
+        assert_eq!(level("2024-01-01 12:00:00 WARN disk almost full"), Some(LogLevel::Warn));
+        assert_eq!(level("[ERROR] failed to bind"), Some(LogLevel::Error));
+        assert_eq!(level("npm ERR! missing script"), Some(LogLevel::Error));
+        assert_eq!(level("error[E0425]: cannot find value"), Some(LogLevel::Error));
+        assert_eq!(level("warning: unused variable"), Some(LogLevel::Warn));
+        // Lower-case words in prose aren't levels.
+        assert_eq!(level("no error so far"), None);
+        assert_eq!(level("compiled successfully"), None);
+    }
+
+    #[test]
+    fn json_records_are_flattened() {
+        let record = parse_record(r#"{"time":"t","msg":"hi","req":{"id":7,"path":"/"}}"#).expect("record");
+        assert_eq!(record.format, RecordFormat::Json);
+        assert_eq!(record.time.as_deref(), Some("t"));
+        assert_eq!(record.get("msg"), Some("hi"));
+        assert_eq!(record.get("req.id"), Some("7"));
+        // Times that can't be read are kept in the summary.
+        assert_eq!(record.summary(), "t hi req.id=7 req.path=/");
+        assert!(parse_record("[1, 2]").is_none());
+    }
+
+    #[test]
+    fn record_times() {
+        let at = |line: &str| parse_record(line).and_then(|r| r.timestamp()).map(|t| t.timestamp_millis());
+        assert_eq!(at(r#"{"time":"2024-01-02T03:04:05.250Z","msg":"x"}"#), Some(1_704_164_645_250));
+        assert_eq!(at(r#"{"time":1704164645250,"msg":"x"}"#), Some(1_704_164_645_250));
+        assert_eq!(at(r#"{"ts":1704164645.25,"msg":"x"}"#), Some(1_704_164_645_250));
+        assert!(at(r#"ts="2024-01-02 03:04:05" msg=x"#).is_some());
+        assert_eq!(at(r#"{"msg":"x"}"#), None);
+        let record = parse_record(r#"{"time":"2024-01-02T03:04:05Z","msg":"ready"}"#).expect("record");
+        assert_eq!(record.summary(), "ready");
+    }
+
+    #[test]
+    fn logfmt_quoting() {
+        let record = parse_record(r#"msg="hello \"world\"" user=bob note="a b""#).expect("record");
+        assert_eq!(record.format, RecordFormat::Logfmt);
+        assert_eq!(record.msg.as_deref(), Some(r#"hello "world""#));
+        assert_eq!(record.get("note"), Some("a b"));
+        assert_eq!(record.summary(), r#"hello "world" user=bob note="a b""#);
+        // Prose with a stray `=` isn't logfmt.
+        assert!(parse_record("x=1").is_none());
+        assert!(parse_record("set x = 1 now").is_none());
+    }
+}
@@ -1,11 +1,31 @@
+mod api;
+mod archive;
+mod buffer;
+mod clipboard;
+mod keys;
+mod list;
+mod limits;
+mod logparse;
+mod metrics;
 mod models;
+mod palette;
+mod ports;
 mod scanner;
 mod events;
+mod headless;
+mod init;
 mod process;
+mod reload;
+mod search;
+mod state;
+mod tree;
 mod ui;
+mod watch;
 mod app;
 mod cli;
 mod config;
+#[cfg(unix)]
+mod daemon;
 
 use anyhow::Result;
 use clap::Parser;
@@ -22,11 +42,68 @@ async fn main() -> Result<()> {
         env::current_dir()?.join(args.path)
     };
 
-    let _config = config::load_config(args.config, &target_dir)?;
+    if let Some(cli::Command::Config { command: cli::ConfigCommand::Schema }) = args.command {
+        return config::print_schema();
+    }
 
-    let services = scanner::scan_directory(&target_dir)?;
+    if let Some(cli::Command::Init { format, force }) = args.command {
+        let services = scanner::scan_directory(&target_dir)?;
+        return init::write_config(&target_dir, &services, format, force);
+    }
 
-    app::run_app(services).await?;
+    let config_path = config::find_config(args.config.as_deref(), &target_dir);
+    let config = config::load_config(args.config, &target_dir)?;
+    // The TUI shows them in its status bar instead.
+    if args.command.is_some() {
+        for warning in &config.warnings {
+            eprintln!("warning: {}", warning);
+        }
+    }
+
+    if let Some(cli::Command::Config { command: cli::ConfigCommand::Show { origin } }) = args.command {
+        return config::print_effective(config_path.as_deref(), &target_dir, origin);
+    }
+
+    if let Some(cli::Command::History { service, run }) = args.command {
+        return archive::print_history(&config.logs.resolve_dir(&target_dir), &service, run);
+    }
+
+    #[cfg(unix)]
+    if let Some(requests) = args.command.as_ref().and_then(daemon::requests_for) {
+        return daemon::control(&target_dir, requests).await;
+    }
+    #[cfg(not(unix))]
+    if matches!(
+        args.command,
+        Some(cli::Command::Daemon { .. } | cli::Command::Start { .. } | cli::Command::Stop { .. } | cli::Command::Restart { .. } | cli::Command::Status | cli::Command::Logs { .. })
+    ) {
+        anyhow::bail!("The daemon needs Unix domain sockets, which this platform lacks");
+    }
+
+    #[cfg(unix)]
+    if let Some(cli::Command::Daemon { detach: true }) = args.command {
+        return daemon::spawn_detached(&target_dir, config_path.as_deref());
+    }
+
+    // Opened from the command palette; created on save if it doesn't exist yet.
+    let config_file = config_path.clone().unwrap_or_else(|| target_dir.join(".devrunner.json"));
+    let services = scanner::discover(&target_dir, &config, &config_file)?;
+
+    #[cfg(unix)]
+    if let Some(cli::Command::Daemon { .. }) = args.command {
+        return daemon::run(services, &config, &target_dir).await;
+    }
+
+    if let Some(cli::Command::List { format }) = args.command {
+        return list::print_services(&services, &target_dir, format);
+    }
+
+    if let Some(cli::Command::Run { services: names, sequential, fail_fast, .. }) = args.command {
+        let code = headless::run(services, &names, sequential, fail_fast).await?;
+        std::process::exit(code);
+    }
+
+    app::run_app(services, config, config_file, target_dir).await?;
 
     Ok(())
 }
@@ -0,0 +1,222 @@
+use crate::models::{Service, ServiceStatus};
+use crate::ports::{self, Listener};
+use std::collections::{HashMap, VecDeque};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+/// Samples kept for the per-service sparklines.
+pub const HISTORY_LEN: usize = 40;
+
+/// Resource usage of a service's whole process tree at one point in time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    /// Sum over the tree; 100 means one fully busy core.
+    pub cpu: f32,
+    /// Resident memory in bytes.
+    pub rss: u64,
+    pub threads: usize,
+    /// Open file descriptors; 0 where the platform doesn't report them.
+    pub fds: usize,
+    pub processes: usize,
+}
+
+/// Latest usage of a running service plus recent history for sparklines.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceMetrics {
+    pub current: Option<Usage>,
+    pub cpu_history: VecDeque<u64>,
+    pub rss_history: VecDeque<u64>,
+    /// Set while usage is above one of the service's alert thresholds.
+    pub alerting: bool,
+    /// Ports the process tree is listening on.
+    pub ports: Vec<Listener>,
+}
+
+impl ServiceMetrics {
+    pub fn record(&mut self, usage: Usage) {
+        self.current = Some(usage);
+        self.cpu_history.push_back(usage.cpu.round() as u64);
+        self.rss_history.push_back(usage.rss);
+        if self.cpu_history.len() > HISTORY_LEN {
+            self.cpu_history.pop_front();
+            self.rss_history.pop_front();
+        }
+    }
+
+    /// Forget the usage of a run that has ended; the history stays visible.
+    pub fn clear_current(&mut self) {
+        self.current = None;
+        self.alerting = false;
+        self.ports.clear();
+    }
+}
+
+/// Refresh the process table with just what `tree_usage` needs.
+pub fn refresh_processes(sys: &mut System) {
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing().with_cpu().with_memory().with_tasks(),
+    );
+}
+
+/// Children of every process, skipping threads (which Linux lists as processes too).
+pub fn child_map(sys: &System) -> HashMap<Pid, Vec<Pid>> {
+    let mut children: HashMap<Pid, Vec<Pid>> = HashMap::new();
+    for (pid, process) in sys.processes() {
+        if process.thread_kind().is_some() {
+            continue;
+        }
+        if let Some(parent) = process.parent() {
+            children.entry(parent).or_default().push(*pid);
+        }
+    }
+    children
+}
+
+/// Processes in the tree rooted at `root`, root first.
+pub fn descendants(children: &HashMap<Pid, Vec<Pid>>, root: Pid) -> Vec<Pid> {
+    let mut tree = vec![root];
+    let mut i = 0;
+    while i < tree.len() {
+        if let Some(kids) = children.get(&tree[i]) {
+            tree.extend(kids);
+        }
+        i += 1;
+    }
+    tree
+}
+
+/// Summed usage of `root` and all of its descendants.
+pub fn tree_usage(sys: &System, children: &HashMap<Pid, Vec<Pid>>, root: u32) -> Usage {
+    let mut usage = Usage::default();
+    for pid in descendants(children, Pid::from_u32(root)) {
+        let Some(process) = sys.process(pid) else {
+            continue;
+        };
+        usage.processes += 1;
+        usage.cpu += process.cpu_usage();
+        usage.rss += process.memory();
+        // The task list may or may not include the main thread itself.
+        usage.threads += process.tasks().map_or(1, |tasks| tasks.len() + usize::from(!tasks.contains(&pid)));
+        usage.fds += process.open_files().unwrap_or(0);
+    }
+    usage
+}
+
+/// Usage and listening ports of one service's process tree.
+pub type Sample = (String, Usage, Vec<Listener>);
+
+/// Names and root pids of the services that are running.
+pub fn running(services: &[Service]) -> Vec<(String, u32)> {
+    services
+        .iter()
+        .filter_map(|s| match s.status {
+            ServiceStatus::Running(pid) if pid > 0 => Some((s.name.clone(), pid)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Sample the process trees rooted at `roots`, as listed by `running`.
+pub fn sample(sys: &mut System, roots: &[(String, u32)]) -> Vec<Sample> {
+    if roots.is_empty() {
+        return Vec::new();
+    }
+    refresh_processes(sys);
+    let children = child_map(sys);
+    let listeners = ports::listeners();
+    roots
+        .iter()
+        .map(|(name, pid)| {
+            let tree: Vec<u32> = descendants(&children, Pid::from_u32(*pid)).iter().map(|p| p.as_u32()).collect();
+            (name.clone(), tree_usage(sys, &children, *pid), ports::listening_ports(&listeners, &tree))
+        })
+        .collect()
+}
+
+/// `sample` on a blocking thread, for loops that must keep serving while
+/// `/proc` is walked. Hands `sys` back for the next round.
+pub async fn sample_blocking(mut sys: System, roots: Vec<(String, u32)>) -> (System, Vec<Sample>) {
+    if roots.is_empty() {
+        return (sys, Vec::new());
+    }
+    tokio::task::spawn_blocking(move || {
+        let samples = sample(&mut sys, &roots);
+        (sys, samples)
+    })
+    .await
+    .expect("metrics sampling panicked")
+}
+
+/// Store samples in the services' metrics. Services that aren't running
+/// any more lose their current usage.
+pub fn record(services: &mut [Service], samples: Vec<Sample>) {
+    for service in services.iter_mut() {
+        if !matches!(service.status, ServiceStatus::Running(_)) {
+            service.metrics.clear_current();
+        }
+    }
+    for (name, usage, ports) in samples {
+        if let Some(service) = services.iter_mut().find(|s| s.name == name && matches!(s.status, ServiceStatus::Running(_))) {
+            service.metrics.record(usage);
+            service.metrics.ports = ports;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProjectType;
+
+    fn service(name: &str, status: ServiceStatus) -> Service {
+        let mut service = Service::new(name.to_string(), "/repo".into(), "/repo/package.json".into(), ProjectType::Node, String::new());
+        service.status = status;
+        service
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut metrics = ServiceMetrics::default();
+        for i in 0..HISTORY_LEN + 5 {
+            metrics.record(Usage { cpu: i as f32, rss: i as u64, ..Usage::default() });
+        }
+        assert_eq!(metrics.cpu_history.len(), HISTORY_LEN);
+        assert_eq!(metrics.rss_history.front(), Some(&5));
+        assert_eq!(metrics.current.map(|u| u.rss), Some(HISTORY_LEN as u64 + 4));
+        metrics.alerting = true;
+        metrics.clear_current();
+        assert!(metrics.current.is_none() && !metrics.alerting);
+        assert_eq!(metrics.cpu_history.len(), HISTORY_LEN);
+    }
+
+    #[test]
+    fn descendants_walk_the_whole_tree() {
+        let pid = Pid::from_u32;
+        let children = HashMap::from([(pid(1), vec![pid(2), pid(3)]), (pid(3), vec![pid(4)]), (pid(9), vec![pid(10)])]);
+        assert_eq!(descendants(&children, pid(1)), [pid(1), pid(2), pid(3), pid(4)]);
+        assert_eq!(descendants(&children, pid(4)), [pid(4)]);
+    }
+
+    #[test]
+    fn samples_are_recorded_for_running_services_only() {
+        let mut services = [service("web", ServiceStatus::Running(10)), service("api", ServiceStatus::Stopped)];
+        services[1].metrics.record(Usage::default());
+        assert_eq!(running(&services), [("web".to_string(), 10)]);
+        let usage = Usage { rss: 42, ..Usage::default() };
+        record(&mut services, vec![("web".to_string(), usage, Vec::new()), ("api".to_string(), usage, Vec::new())]);
+        assert_eq!(services[0].metrics.current.map(|u| u.rss), Some(42));
+        assert!(services[1].metrics.current.is_none());
+    }
+
+    #[test]
+    fn sampling_measures_a_live_process_tree() {
+        let mut sys = System::new();
+        let samples = sample(&mut sys, &[("self".to_string(), std::process::id())]);
+        let (name, usage, _) = &samples[0];
+        assert_eq!(name, "self");
+        assert!(usage.processes >= 1);
+        assert!(usage.rss > 0);
+        assert!(sample(&mut sys, &[]).is_empty());
+    }
+}
@@ -1,5 +1,14 @@
+use crate::buffer::LogBuffer;
+use chrono::{DateTime, Local};
+use crate::config::LimitsConfig;
+use crate::logparse::{self, Record};
+use crate::metrics::ServiceMetrics;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub enum ServiceStatus {
@@ -17,27 +26,156 @@ pub enum ProjectType {
     Unknown,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    /// Messages emitted by DevRunner itself about the service.
+    System,
+}
+
+impl LogStream {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+            LogStream::System => "system",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "stdout" => Some(LogStream::Stdout),
+            "stderr" => Some(LogStream::Stderr),
+            "system" => Some(LogStream::System),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Trace => "TRACE",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+}
+
+/// Time elapsed since DevRunner started; unlike wall-clock time it never
+/// goes backwards, so it gives a stable order across services.
+pub fn monotonic_now() -> Duration {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed()
+}
+
+/// One line of output from a service, or a message DevRunner wrote about it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogLine {
+    pub service: String,
+    pub stream: LogStream,
+    pub timestamp: DateTime<Local>,
+    pub monotonic: Duration,
+    pub raw: String,
+    pub level: Option<LogLevel>,
+    /// Parsed fields when the line is a JSON or logfmt record.
+    pub record: Option<Record>,
+}
+
+impl LogLine {
+    /// Text shown in the log pane: the `msg key=value` summary of a
+    /// structured record, or the raw output otherwise.
+    pub fn display_text(&self) -> Cow<'_, str> {
+        match &self.record {
+            Some(record) => Cow::Owned(record.summary()),
+            None => Cow::Borrowed(&self.raw),
+        }
+    }
+
+    /// A line of program output, with its level detected from the text.
+    pub fn new(service: impl Into<String>, stream: LogStream, raw: impl Into<String>) -> Self {
+        let raw = raw.into();
+        let record = logparse::parse_record(&raw);
+        Self {
+            service: service.into(),
+            stream,
+            timestamp: Local::now(),
+            monotonic: monotonic_now(),
+            level: logparse::detect_level(&raw, record.as_ref()),
+            raw,
+            record,
+        }
+    }
+
+    /// A message from DevRunner itself with a known level.
+    pub fn system(service: impl Into<String>, level: LogLevel, raw: impl Into<String>) -> Self {
+        Self {
+            service: service.into(),
+            stream: LogStream::System,
+            timestamp: Local::now(),
+            monotonic: monotonic_now(),
+            raw: raw.into(),
+            level: Some(level),
+            record: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Service {
     pub name: String,
+    /// Directory the command runs in.
+    #[serde(rename = "cwd")]
     pub path: PathBuf,
+    /// Manifest the service was discovered in, e.g. `web/package.json`.
+    pub manifest: PathBuf,
     pub project_type: ProjectType,
     pub command: String,
     #[serde(skip)]
     pub status: ServiceStatus,
     #[serde(skip)]
-    pub logs: Vec<String>,
+    pub logs: LogBuffer,
+    #[serde(skip)]
+    pub metrics: ServiceMetrics,
+    #[serde(skip)]
+    pub limits: LimitsConfig,
+    #[serde(skip)]
+    pub port: Option<u16>,
+    /// Extra environment variables from the config.
+    #[serde(skip)]
+    pub env: BTreeMap<String, String>,
+    /// No longer found by a rescan, but still running; dropped once it stops.
+    #[serde(skip)]
+    pub stale: bool,
 }
 
 impl Service {
-    pub fn new(name: String, path: PathBuf, project_type: ProjectType, command: String) -> Self {
+    pub fn new(name: String, path: PathBuf, manifest: PathBuf, project_type: ProjectType, command: String) -> Self {
         Self {
             name,
             path,
+            manifest,
             project_type,
             command,
             status: ServiceStatus::Stopped,
-            logs: Vec::new(),
+            logs: LogBuffer::default(),
+            metrics: ServiceMetrics::default(),
+            limits: LimitsConfig::default(),
+            port: None,
+            env: BTreeMap::new(),
+            stale: false,
         }
     }
 }
@@ -0,0 +1,281 @@
+use crate::keys::Action;
+use crate::models::Service;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use std::path::Path;
+
+/// How many recently used entries are remembered per palette.
+pub const RECENT_LIMIT: usize = 20;
+
+/// Everything the command palette can run: every key-bound action plus
+/// commands that only make sense by name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Run(Action),
+    StartGroup,
+    StopGroup,
+    StartAll,
+    StopAll,
+    RestartFailed,
+    ClearLogs,
+    OpenConfig,
+}
+
+impl Command {
+    const EXTRA: [Command; 7] = [
+        Command::StartGroup,
+        Command::StopGroup,
+        Command::StartAll,
+        Command::StopAll,
+        Command::RestartFailed,
+        Command::ClearLogs,
+        Command::OpenConfig,
+    ];
+
+    pub fn all() -> Vec<Command> {
+        let actions = Action::ALL
+            .into_iter()
+            .filter(|a| !matches!(a, Action::CommandPalette | Action::Cancel))
+            .map(Command::Run);
+        Self::EXTRA.into_iter().chain(actions).collect()
+    }
+
+    /// Stable name used to remember recently used commands.
+    pub fn id(self) -> String {
+        match self {
+            Command::Run(action) => action.config_name(),
+            Command::StartGroup => "start_group".to_string(),
+            Command::StopGroup => "stop_group".to_string(),
+            Command::StartAll => "start_all".to_string(),
+            Command::StopAll => "stop_all".to_string(),
+            Command::RestartFailed => "restart_failed".to_string(),
+            Command::ClearLogs => "clear_logs".to_string(),
+            Command::OpenConfig => "open_config".to_string(),
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Command::Run(action) => action.description(),
+            Command::StartGroup => "Start every service in the selected group or project",
+            Command::StopGroup => "Stop every service in the selected group or project",
+            Command::StartAll => "Start all services",
+            Command::StopAll => "Stop all services",
+            Command::RestartFailed => "Restart all failed services",
+            Command::ClearLogs => "Clear logs of the selected service",
+            Command::OpenConfig => "Open config file in $EDITOR",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Service(usize),
+    Command(Command),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaletteKind {
+    Services,
+    Commands,
+}
+
+/// One entry of a palette: `text` is shown prominently, `detail` dimmed
+/// after it, and both are matched against the query.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub text: String,
+    pub detail: String,
+    pub target: Target,
+    /// Position in the recently used list, if any.
+    recent: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub candidate: usize,
+    /// Char indices into `text` followed by `detail` (joined by a space)
+    /// that matched the query.
+    pub indices: Vec<usize>,
+}
+
+/// State of the `Ctrl+P` service finder or the `:` command palette.
+pub struct Palette {
+    pub kind: PaletteKind,
+    pub query: String,
+    pub candidates: Vec<Candidate>,
+    pub matches: Vec<Match>,
+    pub selected: usize,
+}
+
+impl Palette {
+    /// Finder over service names, their paths relative to `root` and project types.
+    pub fn services(services: &[Service], root: &Path, recent: &[String]) -> Self {
+        let candidates = services
+            .iter()
+            .enumerate()
+            .map(|(i, service)| {
+                let path = service.path.strip_prefix(root).unwrap_or(&service.path);
+                let path = if path.as_os_str().is_empty() { ".".into() } else { path.display().to_string() };
+                Candidate {
+                    text: service.name.clone(),
+                    detail: format!("{} · {:?}", path, service.project_type),
+                    target: Target::Service(i),
+                    recent: recent.iter().position(|name| *name == service.name),
+                }
+            })
+            .collect();
+        Self::new(PaletteKind::Services, candidates)
+    }
+
+    pub fn commands(recent: &[String]) -> Self {
+        let candidates = Command::all()
+            .into_iter()
+            .map(|command| {
+                let id = command.id();
+                Candidate {
+                    text: command.title().to_string(),
+                    detail: id.clone(),
+                    target: Target::Command(command),
+                    recent: recent.iter().position(|r| *r == id),
+                }
+            })
+            .collect();
+        Self::new(PaletteKind::Commands, candidates)
+    }
+
+    fn new(kind: PaletteKind, candidates: Vec<Candidate>) -> Self {
+        let mut palette = Self {
+            kind,
+            query: String::new(),
+            candidates,
+            matches: Vec::new(),
+            selected: 0,
+        };
+        palette.update();
+        palette
+    }
+
+    /// Re-rank the candidates for the current query: best fuzzy score first,
+    /// ties (and the empty query) broken by how recently they were used.
+    pub fn update(&mut self) {
+        let matcher = SkimMatcherV2::default().ignore_case();
+        let mut scored: Vec<(i64, Match)> = self
+            .candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, candidate)| {
+                let haystack = format!("{} {}", candidate.text, candidate.detail);
+                let (score, indices) = if self.query.is_empty() {
+                    (0, Vec::new())
+                } else {
+                    matcher.fuzzy_indices(&haystack, &self.query)?
+                };
+                Some((score, Match { candidate: i, indices }))
+            })
+            .collect();
+        let candidates = &self.candidates;
+        scored.sort_by_key(|(score, m)| {
+            let recent = candidates[m.candidate].recent.unwrap_or(usize::MAX);
+            (-score, recent, m.candidate)
+        });
+        self.matches = scored.into_iter().map(|(_, m)| m).collect();
+        self.selected = 0;
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + 1) % self.matches.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + self.matches.len() - 1) % self.matches.len();
+        }
+    }
+
+    pub fn target(&self) -> Option<Target> {
+        let m = self.matches.get(self.selected)?;
+        Some(self.candidates[m.candidate].target)
+    }
+}
+
+/// Move `entry` to the front of a recently used list.
+pub fn remember(recent: &mut Vec<String>, entry: String) {
+    recent.retain(|r| *r != entry);
+    recent.insert(0, entry);
+    recent.truncate(RECENT_LIMIT);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProjectType;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    fn service(name: &str, dir: &str) -> Service {
+        let path = PathBuf::from("/repo").join(dir);
+        Service::new(name.to_string(), path.clone(), path.join("package.json"), ProjectType::Node, String::new())
+    }
+
+    fn ranked(palette: &Palette) -> Vec<&str> {
+        palette.matches.iter().map(|m| palette.candidates[m.candidate].text.as_str()).collect()
+    }
+
+    #[test]
+    fn command_ids_are_unique_config_names() {
+        let ids: HashSet<String> = Command::all().into_iter().map(Command::id).collect();
+        assert_eq!(ids.len(), Command::all().len());
+        assert_eq!(Command::Run(Action::ToggleWatch).id(), "toggle_watch");
+        assert!(!ids.contains("command_palette"));
+    }
+
+    #[test]
+    fn empty_query_lists_recent_entries_first() {
+        let services = [service("api", "api"), service("web", "web"), service("docs", "docs")];
+        let palette = Palette::services(&services, Path::new("/repo"), &["docs".to_string(), "web".to_string()]);
+        assert_eq!(ranked(&palette), ["docs", "web", "api"]);
+    }
+
+    #[test]
+    fn better_matches_rank_above_recent_ones() {
+        let services = [service("web", "apps/web"), service("worker", "apps/worker")];
+        let mut palette = Palette::services(&services, Path::new("/repo"), &["worker".to_string()]);
+        palette.query = "web".to_string();
+        palette.update();
+        assert_eq!(ranked(&palette)[0], "web");
+        assert_eq!(palette.target(), Some(Target::Service(0)));
+        // Paths are matched too.
+        palette.query = "apps/wor".to_string();
+        palette.update();
+        assert_eq!(ranked(&palette), ["worker"]);
+        palette.query = "zzz".to_string();
+        palette.update();
+        assert!(palette.matches.is_empty());
+        assert_eq!(palette.target(), None);
+    }
+
+    #[test]
+    fn selection_wraps_around() {
+        let services = [service("a", "a"), service("b", "b")];
+        let mut palette = Palette::services(&services, Path::new("/repo"), &[]);
+        palette.select_previous();
+        assert_eq!(palette.selected, 1);
+        palette.select_next();
+        assert_eq!(palette.selected, 0);
+    }
+
+    #[test]
+    fn remember_moves_entries_to_the_front_and_caps_the_list() {
+        let mut recent: Vec<String> = (0..RECENT_LIMIT).map(|i| i.to_string()).collect();
+        remember(&mut recent, "5".to_string());
+        assert_eq!(recent[..2], ["5", "0"]);
+        assert_eq!(recent.len(), RECENT_LIMIT);
+        remember(&mut recent, "new".to_string());
+        assert_eq!(recent[0], "new");
+        assert_eq!(recent.len(), RECENT_LIMIT);
+        assert!(!recent.contains(&(RECENT_LIMIT - 1).to_string()));
+    }
+}
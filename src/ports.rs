@@ -0,0 +1,212 @@
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// A socket accepting connections (TCP) or bound without a peer (UDP).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Listener {
+    pub protocol: Protocol,
+    pub port: u16,
+    inode: u64,
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.protocol {
+            Protocol::Tcp => write!(f, ":{}", self.port),
+            Protocol::Udp => write!(f, ":{}/udp", self.port),
+        }
+    }
+}
+
+/// A process found holding a port.
+#[derive(Debug, Clone)]
+pub struct Holder {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// Read one `/proc/net/{tcp,udp}[6]` table, keeping listening sockets.
+#[cfg(target_os = "linux")]
+fn read_table(path: &str, protocol: Protocol) -> Vec<Listener> {
+    std::fs::read_to_string(path).map_or_else(|_| Vec::new(), |content| parse_table(&content, protocol))
+}
+
+/// Listening sockets of a `/proc/net` table: addresses are `ip:port` in
+/// hex, and sockets without an inode are skipped.
+#[cfg(target_os = "linux")]
+fn parse_table(content: &str, protocol: Protocol) -> Vec<Listener> {
+    // TCP_LISTEN, and TCP_CLOSE which is how unconnected UDP sockets show up.
+    let listening = match protocol {
+        Protocol::Tcp => "0A",
+        Protocol::Udp => "07",
+    };
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (local, state, inode) = (fields.get(1)?, fields.get(3)?, fields.get(9)?);
+            if *state != listening {
+                return None;
+            }
+            let port = u16::from_str_radix(local.rsplit(':').next()?, 16).ok()?;
+            let inode = inode.parse().ok().filter(|&i| i != 0)?;
+            Some(Listener { protocol, port, inode })
+        })
+        .collect()
+}
+
+/// Every listening socket on the machine.
+#[cfg(target_os = "linux")]
+pub fn listeners() -> Vec<Listener> {
+    let mut all = Vec::new();
+    for (path, protocol) in [
+        ("/proc/net/tcp", Protocol::Tcp),
+        ("/proc/net/tcp6", Protocol::Tcp),
+        ("/proc/net/udp", Protocol::Udp),
+        ("/proc/net/udp6", Protocol::Udp),
+    ] {
+        all.extend(read_table(path, protocol));
+    }
+    // The same port bound on IPv4 and IPv6 counts once.
+    let mut seen = HashSet::new();
+    all.retain(|l| seen.insert((l.protocol, l.port, l.inode)));
+    all
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn listeners() -> Vec<Listener> {
+    Vec::new()
+}
+
+/// Socket inodes held open by a process, from its `/proc/<pid>/fd` links.
+#[cfg(target_os = "linux")]
+fn socket_inodes(pid: u32) -> Vec<u64> {
+    let Ok(entries) = std::fs::read_dir(format!("/proc/{}/fd", pid)) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let target = std::fs::read_link(entry.ok()?.path()).ok()?;
+            let target = target.to_str()?;
+            target.strip_prefix("socket:[")?.strip_suffix(']')?.parse().ok()
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn socket_inodes(_pid: u32) -> Vec<u64> {
+    Vec::new()
+}
+
+/// Ports the given processes are listening on, sorted and deduplicated.
+pub fn listening_ports(all: &[Listener], pids: &[u32]) -> Vec<Listener> {
+    let inodes: HashSet<u64> = pids.iter().flat_map(|&pid| socket_inodes(pid)).collect();
+    let mut ports: Vec<Listener> = all.iter().filter(|l| inodes.contains(&l.inode)).copied().collect();
+    ports.sort_by_key(|l| (l.port, l.protocol == Protocol::Udp));
+    ports.dedup_by_key(|l| (l.port, l.protocol));
+    ports
+}
+
+/// The process listening on a TCP `port`, or `None` if the port is free.
+/// A holder we aren't allowed to inspect is reported with pid 0.
+pub fn tcp_port_holder(port: u16) -> Option<Holder> {
+    let inodes: HashSet<u64> = listeners()
+        .into_iter()
+        .filter(|l| l.protocol == Protocol::Tcp && l.port == port)
+        .map(|l| l.inode)
+        .collect();
+    if inodes.is_empty() {
+        return None;
+    }
+    let unknown = Holder { pid: 0, name: "an unknown process".to_string() };
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return Some(unknown);
+    };
+    for entry in procs.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) else {
+            continue;
+        };
+        if socket_inodes(pid).iter().any(|inode| inodes.contains(inode)) {
+            let name = std::fs::read_to_string(format!("/proc/{}/comm", pid))
+                .map(|n| n.trim().to_string())
+                .unwrap_or_default();
+            return Some(Holder { pid, name });
+        }
+    }
+    Some(unknown)
+}
+
+/// Ask the process holding TCP `port` to exit, killing it if the port is
+/// still taken after a few seconds. Returns whether the port came free.
+#[cfg(unix)]
+pub async fn kill_holder(pid: u32, port: u16) -> bool {
+    use std::time::Duration;
+
+    for signal in [libc::SIGTERM, libc::SIGKILL] {
+        unsafe {
+            libc::kill(pid as libc::pid_t, signal);
+        }
+        for _ in 0..25 {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            if tcp_port_holder(port).is_none() {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+#[cfg(not(unix))]
+pub async fn kill_holder(_pid: u32, port: u16) -> bool {
+    tcp_port_holder(port).is_none()
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    const TCP: &str = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:0BB8 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41234 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41235 1 0000000000000000 100 0 0 10 0
+   2: 0100007F:D2F0 0100007F:0BB8 01 00000000:00000000 00:00000000 00000000  1000        0 41236 1 0000000000000000 20 4 30 10 -1
+   3: 00000000:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 0 1 0000000000000000 100 0 0 10 0
+";
+
+    #[test]
+    fn decodes_hex_ports_of_listening_sockets() {
+        let listeners = parse_table(TCP, Protocol::Tcp);
+        let found: Vec<(u16, u64)> = listeners.iter().map(|l| (l.port, l.inode)).collect();
+        // 0x0BB8 and 0x1F90; the established socket and the one without an
+        // inode are skipped.
+        assert_eq!(found, [(3000, 41234), (8080, 41235)]);
+        assert_eq!(listeners[0].to_string(), ":3000");
+    }
+
+    #[test]
+    fn udp_sockets_listen_in_the_close_state() {
+        let udp = "\
+   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  123: 00000000:14E9 00000000:0000 07 00000000:00000000 00:00000000 00000000   101        0 5150 2 0000000000000000 0
+";
+        let listeners = parse_table(udp, Protocol::Udp);
+        assert_eq!(listeners.len(), 1);
+        assert_eq!(listeners[0].to_string(), ":5353/udp");
+        assert!(parse_table(TCP, Protocol::Udp).is_empty());
+    }
+
+    #[test]
+    fn finds_the_ports_of_this_process() {
+        let socket = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let port = socket.local_addr().expect("address").port();
+        let ports = listening_ports(&listeners(), &[std::process::id()]);
+        assert!(ports.iter().any(|l| l.protocol == Protocol::Tcp && l.port == port));
+    }
+}
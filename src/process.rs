@@ -1,19 +1,98 @@
-use crate::models::{Service, ServiceStatus};
+use crate::models::{LogLevel, LogLine, LogStream, Service, ServiceStatus};
 use crate::events::Event;
+use crate::limits;
 use anyhow::Result;
+use std::collections::HashMap;
 use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
+use tokio::process::{Child, Command};
 use tokio::sync::mpsc::UnboundedSender;
-use chrono::Local;
+use tokio::sync::oneshot;
+
+/// How long a stopped service gets to exit after SIGTERM before it is killed.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Handle to a running service's task, used to ask it to stop.
+struct RunningProcess {
+    run_id: u64,
+    pid: Option<u32>,
+    stop_tx: oneshot::Sender<()>,
+}
 
 pub struct ProcessManager {
     event_tx: UnboundedSender<Event>,
+    running: Arc<Mutex<HashMap<String, RunningProcess>>>,
+    next_run_id: AtomicU64,
+}
+
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: libc::c_int) {
+    // The service was spawned as the leader of its own process group, so
+    // signalling the negated pid reaches everything it started as well.
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), signal);
+    }
+}
+
+#[cfg(unix)]
+async fn terminate(child: &mut Child) {
+    let Some(pid) = child.id() else {
+        return;
+    };
+    signal_group(pid, libc::SIGTERM);
+    if tokio::time::timeout(STOP_TIMEOUT, child.wait()).await.is_err() {
+        signal_group(pid, libc::SIGKILL);
+    }
 }
 
+#[cfg(not(unix))]
+async fn terminate(child: &mut Child) {
+    let _ = child.kill().await;
+}
+
+/// Kill a service and everything it started without waiting for a graceful
+/// stop, e.g. after a second Ctrl+C.
+#[cfg(unix)]
+pub fn kill(pid: u32) {
+    signal_group(pid, libc::SIGKILL);
+}
+
+/// Elsewhere stopping a service already kills it outright.
+#[cfg(not(unix))]
+pub fn kill(_pid: u32) {}
+
 impl ProcessManager {
     pub fn new(event_tx: UnboundedSender<Event>) -> Self {
-        Self { event_tx }
+        Self {
+            event_tx,
+            running: Arc::new(Mutex::new(HashMap::new())),
+            next_run_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Ask a running service to stop. Returns false if it wasn't running.
+    pub fn stop_service(&self, name: &str) -> bool {
+        let process = self.running.lock().unwrap().remove(name);
+        match process {
+            Some(process) => process.stop_tx.send(()).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Terminate every running service immediately, without waiting for
+    /// their tasks; used when DevRunner itself is exiting.
+    pub fn shutdown(&self) {
+        let running: Vec<RunningProcess> = self.running.lock().unwrap().drain().map(|(_, p)| p).collect();
+        for process in running {
+            #[cfg(unix)]
+            if let Some(pid) = process.pid {
+                signal_group(pid, libc::SIGTERM);
+            }
+            let _ = process.stop_tx.send(());
+        }
     }
 
     pub async fn spawn_service(&self, service: Service) -> Result<()> {
@@ -21,45 +100,61 @@ impl ProcessManager {
         let service_name = service.name.clone();
         let command_str = service.command.clone();
         let path = service.path.clone();
+        let env = service.env.clone();
+        let limits = service.limits.clone();
+
+        let running = self.running.clone();
+        let run_id = self.next_run_id.fetch_add(1, Ordering::Relaxed);
 
         event_tx.send(Event::ServiceStatus(service_name.clone(), ServiceStatus::Running(0)))?;
 
         tokio::spawn(async move {
-            let child = if cfg!(target_os = "windows") {
-                Command::new("cmd")
-                    .args(&["/C", &command_str])
-                    .current_dir(&path)
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .spawn()
+            let mut command = if cfg!(target_os = "windows") {
+                let mut command = Command::new("cmd");
+                command.args(["/C", &command_str]);
+                command
             } else {
-                Command::new("sh")
-                    .args(&["-c", &command_str])
-                    .current_dir(&path)
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .spawn()
+                let mut command = Command::new("sh");
+                command.args(["-c", &command_str]);
+                command
             };
+            command
+                .current_dir(&path)
+                .envs(&env)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            #[cfg(unix)]
+            command.process_group(0);
+            let enforcement = limits::apply(&mut command, &service_name, &limits);
+            let child = command.spawn();
 
             let mut child = match child {
                 Ok(c) => c,
                 Err(e) => {
-                    let timestamp = Local::now().format("%H:%M:%S").to_string();
                     let _ = event_tx.send(Event::ServiceLog(
-                        service_name.clone(),
-                        format!("{} [ERROR] Failed to start: {}", timestamp, e)
+                        LogLine::system(&service_name, LogLevel::Error, format!("Failed to start: {}", e))
                     ));
                     let _ = event_tx.send(Event::ServiceStatus(service_name.clone(), ServiceStatus::Failed));
+                    enforcement.cleanup();
                     return;
                 }
             };
             
+            let (stop_tx, mut stop_rx) = oneshot::channel();
+            running.lock().unwrap().insert(
+                service_name.clone(),
+                RunningProcess { run_id, pid: child.id(), stop_tx },
+            );
+
             if let Some(id) = child.id() {
-                let timestamp = Local::now().format("%H:%M:%S").to_string();
                 let _ = event_tx.send(Event::ServiceStatus(service_name.clone(), ServiceStatus::Running(id)));
                 let _ = event_tx.send(Event::ServiceLog(
-                    service_name.clone(),
-                    format!("{} [INFO] {} started successfully.", timestamp, service_name)
+                    LogLine::system(&service_name, LogLevel::Info, format!("{} started successfully.", service_name))
+                ));
+            }
+            if !enforcement.summary.is_empty() {
+                let _ = event_tx.send(Event::ServiceLog(
+                    LogLine::system(&service_name, LogLevel::Info, format!("Limits: {}", enforcement.summary.join(", ")))
                 ));
             }
 
@@ -74,10 +169,8 @@ impl ProcessManager {
             
             let stdout_task = tokio::spawn(async move {
                 while let Ok(Some(line)) = stdout_reader.next_line().await {
-                    let timestamp = Local::now().format("%H:%M:%S").to_string();
                     let _ = tx_clone.send(Event::ServiceLog(
-                        name_clone.clone(),
-                        format!("{} [EXEC] {}", timestamp, line)
+                        LogLine::new(&name_clone, LogStream::Stdout, line)
                     ));
                 }
             });
@@ -87,40 +180,57 @@ impl ProcessManager {
             
             let stderr_task = tokio::spawn(async move {
                 while let Ok(Some(line)) = stderr_reader.next_line().await {
-                    let timestamp = Local::now().format("%H:%M:%S").to_string();
                     let _ = tx_clone.send(Event::ServiceLog(
-                        name_clone.clone(),
-                        format!("{} [ERROR] {}", timestamp, line)
+                        LogLine::new(&name_clone, LogStream::Stderr, line)
                     ));
                 }
             });
             
-            let status = child.wait().await;
-            
+            let mut stopped = false;
+            let status = tokio::select! {
+                status = child.wait() => status,
+                _ = &mut stop_rx => {
+                    stopped = true;
+                    terminate(&mut child).await;
+                    child.wait().await
+                }
+            };
+
             let _ = stdout_task.await;
             let _ = stderr_task.await;
+            enforcement.cleanup();
+
+            {
+                let mut running = running.lock().unwrap();
+                if running.get(&service_name).is_some_and(|p| p.run_id == run_id) {
+                    running.remove(&service_name);
+                }
+            }
 
-            let timestamp = Local::now().format("%H:%M:%S").to_string();
             match status {
+                Ok(_) if stopped => {
+                    let _ = event_tx.send(Event::ServiceLog(
+                        LogLine::system(&service_name, LogLevel::Info, "Process stopped.")
+                    ));
+                    let _ = event_tx.send(Event::ServiceStatus(service_name, ServiceStatus::Stopped));
+                }
                 Ok(s) => {
+                    let _ = event_tx.send(Event::ServiceExit(service_name.clone(), s.code()));
                     if s.success() {
                         let _ = event_tx.send(Event::ServiceLog(
-                            service_name.clone(),
-                            format!("{} [INFO] Process completed successfully.", timestamp)
+                            LogLine::system(&service_name, LogLevel::Info, "Process completed successfully.")
                         ));
                         let _ = event_tx.send(Event::ServiceStatus(service_name, ServiceStatus::Completed));
                     } else {
                         let _ = event_tx.send(Event::ServiceLog(
-                            service_name.clone(),
-                            format!("{} [ERROR] Process failed with exit code: {:?}", timestamp, s.code())
+                            LogLine::system(&service_name, LogLevel::Error, format!("Process failed with exit code: {:?}", s.code()))
                         ));
                         let _ = event_tx.send(Event::ServiceStatus(service_name, ServiceStatus::Failed));
                     }
                 }
                 Err(e) => {
                     let _ = event_tx.send(Event::ServiceLog(
-                        service_name.clone(),
-                        format!("{} [ERROR] Process error: {}", timestamp, e)
+                        LogLine::system(&service_name, LogLevel::Error, format!("Process error: {}", e))
                     ));
                     let _ = event_tx.send(Event::ServiceStatus(service_name, ServiceStatus::Failed));
                 }
@@ -0,0 +1,173 @@
+use crate::config::{AppConfig, LimitsConfig, WatchConfig};
+use crate::models::Service;
+use std::collections::BTreeMap;
+
+/// What a config reload can change about a service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    command: String,
+    env: BTreeMap<String, String>,
+    port: Option<u16>,
+    limits: LimitsConfig,
+    watch: Option<WatchConfig>,
+}
+
+/// Current settings of every service still in the project, by name.
+pub fn snapshot(services: &[Service], config: &AppConfig) -> BTreeMap<String, Settings> {
+    services
+        .iter()
+        .filter(|s| !s.stale)
+        .map(|s| {
+            let settings = Settings {
+                command: s.command.clone(),
+                env: s.env.clone(),
+                port: s.port,
+                limits: s.limits.clone(),
+                watch: config.watch(&s.name).cloned(),
+            };
+            (s.name.clone(), settings)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// One service's entry in the diff shown after a reload.
+#[derive(Debug, Clone)]
+pub struct ServiceChange {
+    pub service: String,
+    pub kind: ChangeKind,
+    /// What changed, one line each; empty unless modified.
+    pub details: Vec<String>,
+    /// The command or environment changed, so a running service is still
+    /// running the old one.
+    pub needs_restart: bool,
+}
+
+fn show_port(port: Option<u16>) -> String {
+    port.map_or("none".to_string(), |p| p.to_string())
+}
+
+fn modified(old: &Settings, new: &Settings) -> Vec<String> {
+    let mut details = Vec::new();
+    if old.command != new.command {
+        details.push(format!("command: {} → {}", old.command, new.command));
+    }
+    for (key, value) in &new.env {
+        match old.env.get(key) {
+            None => details.push(format!("env: +{}={}", key, value)),
+            Some(previous) if previous != value => details.push(format!("env: {}={} → {}", key, previous, value)),
+            Some(_) => {}
+        }
+    }
+    for key in old.env.keys().filter(|k| !new.env.contains_key(*k)) {
+        details.push(format!("env: -{}", key));
+    }
+    if old.port != new.port {
+        details.push(format!("port: {} → {}", show_port(old.port), show_port(new.port)));
+    }
+    if old.limits != new.limits {
+        details.push("limits changed".to_string());
+    }
+    if old.watch != new.watch {
+        details.push("watch settings changed".to_string());
+    }
+    details
+}
+
+/// Services added, removed or modified between two snapshots.
+pub fn diff(old: &BTreeMap<String, Settings>, new: &BTreeMap<String, Settings>) -> Vec<ServiceChange> {
+    let mut changes = Vec::new();
+    for (name, settings) in new {
+        let change = match old.get(name) {
+            None => ServiceChange { service: name.clone(), kind: ChangeKind::Added, details: Vec::new(), needs_restart: false },
+            Some(previous) if previous != settings => ServiceChange {
+                service: name.clone(),
+                kind: ChangeKind::Modified,
+                details: modified(previous, settings),
+                needs_restart: previous.command != settings.command || previous.env != settings.env,
+            },
+            Some(_) => continue,
+        };
+        changes.push(change);
+    }
+    for name in old.keys().filter(|n| !new.contains_key(*n)) {
+        changes.push(ServiceChange { service: name.clone(), kind: ChangeKind::Removed, details: Vec::new(), needs_restart: false });
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_config;
+    use crate::models::ProjectType;
+    use std::path::{Path, PathBuf};
+
+    fn services(names: &[&str], config: &str) -> (Vec<Service>, AppConfig) {
+        let config = parse_config(Path::new("devrunner.toml"), config).expect("valid config");
+        let services = names
+            .iter()
+            .map(|name| {
+                let manifest = PathBuf::from("web/package.json");
+                let mut service = Service::new(name.to_string(), "web".into(), manifest, ProjectType::Node, "npm run dev".into());
+                config.apply(&mut service);
+                service
+            })
+            .collect();
+        (services, config)
+    }
+
+    fn changes(old: (&[&str], &str), new: (&[&str], &str)) -> Vec<ServiceChange> {
+        let (old_services, old_config) = services(old.0, old.1);
+        let (new_services, new_config) = services(new.0, new.1);
+        diff(&snapshot(&old_services, &old_config), &snapshot(&new_services, &new_config))
+    }
+
+    #[test]
+    fn unchanged_config_has_no_changes() {
+        let config = "[services.\"web: dev\"]\nport = 3000\n";
+        assert!(changes((&["web: dev"], config), (&["web: dev"], config)).is_empty());
+    }
+
+    #[test]
+    fn command_and_env_changes_need_a_restart() {
+        let old = "[services.\"web: dev\"]\nenv = { A = \"1\", B = \"2\" }\n";
+        let new = "[services.\"web: dev\"]\ncommand = \"vite\"\nenv = { A = \"3\", C = \"4\" }\n";
+        let changes = changes((&["web: dev"], old), (&["web: dev"], new));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, ChangeKind::Modified);
+        assert!(changes[0].needs_restart);
+        assert_eq!(changes[0].details, ["command: npm run dev → vite", "env: A=1 → 3", "env: +C=4", "env: -B"]);
+    }
+
+    #[test]
+    fn port_limits_and_watch_changes_apply_without_a_restart() {
+        let new = "[services.\"web: dev\"]\nport = 4000\nlimits = { memory_mb = 512 }\nwatch = { paths = [\"src/**\"] }\n";
+        let changes = changes((&["web: dev"], ""), (&["web: dev"], new));
+        assert_eq!(changes.len(), 1);
+        assert!(!changes[0].needs_restart);
+        assert_eq!(changes[0].details, ["port: none → 4000", "limits changed", "watch settings changed"]);
+    }
+
+    #[test]
+    fn added_and_removed_services() {
+        let changes = changes((&["web: dev", "api: run"], ""), (&["web: dev", "docs: serve"], ""));
+        let kinds: Vec<(&str, ChangeKind)> = changes.iter().map(|c| (c.service.as_str(), c.kind)).collect();
+        assert_eq!(kinds, [("docs: serve", ChangeKind::Added), ("api: run", ChangeKind::Removed)]);
+        assert!(changes.iter().all(|c| !c.needs_restart && c.details.is_empty()));
+    }
+
+    #[test]
+    fn stale_services_are_left_out() {
+        let (mut services, config) = services(&["web: dev", "api: run"], "");
+        services[1].stale = true;
+        let snapshot = snapshot(&services, &config);
+        assert_eq!(snapshot.keys().collect::<Vec<_>>(), ["web: dev"]);
+    }
+}
@@ -1,9 +1,38 @@
+use crate::config::AppConfig;
 use crate::models::{ProjectType, Service};
 use anyhow::Result;
 use std::fs;
 use std::path::Path;
 use walkdir::WalkDir;
 
+const PACKAGE_JSON: &str = "package.json";
+const CARGO_TOML: &str = "Cargo.toml";
+
+/// Whether services can be discovered in this file.
+pub fn is_manifest(path: &Path) -> bool {
+    path.file_name().is_some_and(|n| n == PACKAGE_JSON || n == CARGO_TOML)
+        && !path.components().any(|c| c.as_os_str() == "node_modules" || c.as_os_str() == "target")
+}
+
+/// Every service of the project: those found in manifests, then the
+/// config's `custom_scripts`, with the config's per-service settings applied.
+pub fn discover(root: &Path, config: &AppConfig, config_path: &Path) -> Result<Vec<Service>> {
+    let mut services = scan_directory(root)?;
+    services.extend(config.custom_scripts.iter().map(|script| {
+        Service::new(
+            format!("custom: {}", script.name),
+            root.to_path_buf(),
+            config_path.to_path_buf(),
+            ProjectType::Unknown,
+            script.command.clone(),
+        )
+    }));
+    for service in &mut services {
+        config.apply(service);
+    }
+    Ok(services)
+}
+
 pub fn scan_directory(root: &Path) -> Result<Vec<Service>> {
     let mut services = Vec::new();
 
@@ -20,14 +49,14 @@ pub fn scan_directory(root: &Path) -> Result<Vec<Service>> {
             continue;
         }
 
-        if path.file_name() == Some("package.json".as_ref()) {
+        if path.file_name() == Some(PACKAGE_JSON.as_ref()) {
             if let Ok(found_services) = parse_package_json(path) {
                 services.extend(found_services);
             }
-        } else if path.file_name() == Some("Cargo.toml".as_ref()) {
-             if let Ok(found_services) = parse_cargo_toml(path) {
-                services.extend(found_services);
-             }
+        } else if path.file_name() == Some(CARGO_TOML.as_ref())
+            && let Ok(found_services) = parse_cargo_toml(path)
+        {
+            services.extend(found_services);
         }
     }
 
@@ -50,6 +79,7 @@ fn parse_package_json(path: &Path) -> Result<Vec<Service>> {
                  services.push(Service::new(
                      format!("{}: {}", package_name, script),
                      dir_path.clone(),
+                     path.to_path_buf(),
                      ProjectType::Node,
                      format!("npm run {}", script),
                  ));
@@ -75,6 +105,7 @@ fn parse_cargo_toml(path: &Path) -> Result<Vec<Service>> {
              services.push(Service::new(
                  format!("{}: run", name),
                  dir_path.clone(),
+                 path.to_path_buf(),
                  ProjectType::Rust,
                  "cargo run".to_string(),
              ));
@@ -0,0 +1,360 @@
+use crate::logparse::level_from_name;
+use crate::models::{LogLine, LogStream};
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+/// Which output stream a filter lets through.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StreamFilter {
+    #[default]
+    All,
+    Stdout,
+    Stderr,
+}
+
+impl StreamFilter {
+    pub fn cycle(self) -> Self {
+        match self {
+            StreamFilter::All => StreamFilter::Stdout,
+            StreamFilter::Stdout => StreamFilter::Stderr,
+            StreamFilter::Stderr => StreamFilter::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StreamFilter::All => "all",
+            StreamFilter::Stdout => "stdout",
+            StreamFilter::Stderr => "stderr",
+        }
+    }
+
+    fn accepts(self, stream: LogStream) -> bool {
+        match self {
+            StreamFilter::All => true,
+            StreamFilter::Stdout => stream == LogStream::Stdout,
+            StreamFilter::Stderr => stream == LogStream::Stderr,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Substring(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+}
+
+impl CmpOp {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            CmpOp::Eq => ordering == Ordering::Equal,
+            CmpOp::Ne => ordering != Ordering::Equal,
+            CmpOp::Ge => ordering != Ordering::Less,
+            CmpOp::Le => ordering != Ordering::Greater,
+            CmpOp::Gt => ordering == Ordering::Greater,
+            CmpOp::Lt => ordering == Ordering::Less,
+        }
+    }
+}
+
+/// A `key<op>value` condition on a structured field, e.g. `level>=warn` or
+/// `req.id=abc`.
+#[derive(Debug, Clone)]
+struct FieldCondition {
+    key: String,
+    op: CmpOp,
+    value: String,
+}
+
+impl FieldCondition {
+    fn parse(term: &str) -> Option<Self> {
+        let idx = term.find(['=', '!', '<', '>'])?;
+        let (key, rest) = term.split_at(idx);
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || "_.@-".contains(c)) {
+            return None;
+        }
+        let (op, value) = [
+            (">=", CmpOp::Ge),
+            ("<=", CmpOp::Le),
+            ("!=", CmpOp::Ne),
+            ("=", CmpOp::Eq),
+            (">", CmpOp::Gt),
+            ("<", CmpOp::Lt),
+        ]
+        .into_iter()
+        .find_map(|(token, op)| rest.strip_prefix(token).map(|v| (op, v)))?;
+        Some(Self {
+            key: key.to_string(),
+            op,
+            value: value.trim_matches('"').to_string(),
+        })
+    }
+
+    fn matches(&self, line: &LogLine) -> bool {
+        if self.key == "level" {
+            return match (line.level, level_from_name(&self.value)) {
+                (Some(actual), Some(wanted)) => self.op.holds(actual.cmp(&wanted)),
+                _ => self.op == CmpOp::Ne,
+            };
+        }
+        let Some(actual) = line.record.as_ref().and_then(|r| r.get(&self.key)) else {
+            return self.op == CmpOp::Ne;
+        };
+        let ordering = match (actual.parse::<f64>(), self.value.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            _ => actual.to_lowercase().cmp(&self.value.to_lowercase()),
+        };
+        self.op.holds(ordering)
+    }
+}
+
+/// A persistent filter narrowing which log lines are displayed.
+///
+/// The expression is a list of whitespace-separated terms. Terms of the form
+/// `key<op>value` (`=`, `!=`, `>=`, `<=`, `>`, `<`) match fields of JSON or
+/// logfmt lines, with `level` comparing detected severities. The remaining
+/// text is `[!]pattern` for a case-insensitive substring; alternatively the
+/// whole expression may be `[!]/regex/`. A leading `!` excludes matches
+/// instead of keeping them. Patterns are matched against the displayed text,
+/// so structured lines match their `msg key=value` summary.
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    pub expression: String,
+    pub stream: StreamFilter,
+    pattern: Option<Pattern>,
+    conditions: Vec<FieldCondition>,
+    exclude: bool,
+}
+
+impl LogFilter {
+    pub fn parse(expression: &str, stream: StreamFilter) -> Result<Self> {
+        let trimmed = expression.trim();
+        let unnegated = trimmed.strip_prefix('!').unwrap_or(trimmed);
+        let is_regex = unnegated.len() >= 2 && unnegated.starts_with('/') && unnegated.ends_with('/');
+        let mut conditions = Vec::new();
+        let text = if is_regex {
+            trimmed.to_string()
+        } else {
+            let mut rest = Vec::new();
+            for term in trimmed.split_whitespace() {
+                match FieldCondition::parse(term) {
+                    Some(condition) => conditions.push(condition),
+                    None => rest.push(term),
+                }
+            }
+            rest.join(" ")
+        };
+        let (exclude, body) = match text.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, text.as_str()),
+        };
+        let pattern = if body.is_empty() {
+            if exclude {
+                bail!("nothing to exclude after '!'");
+            }
+            None
+        } else if let Some(re) = body.strip_prefix('/').and_then(|b| b.strip_suffix('/')) {
+            if re.is_empty() {
+                bail!("empty regex");
+            }
+            Some(Pattern::Regex(RegexBuilder::new(re).case_insensitive(true).build()?))
+        } else {
+            Some(Pattern::Substring(body.to_lowercase()))
+        };
+        Ok(Self {
+            expression: expression.to_string(),
+            stream,
+            pattern,
+            conditions,
+            exclude,
+        })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.pattern.is_some() || !self.conditions.is_empty() || self.stream != StreamFilter::All
+    }
+
+    pub fn matches(&self, line: &LogLine) -> bool {
+        if !self.stream.accepts(line.stream) {
+            return false;
+        }
+        if !self.conditions.iter().all(|c| c.matches(line)) {
+            return false;
+        }
+        let hit = match &self.pattern {
+            None => return true,
+            Some(Pattern::Substring(needle)) => line.display_text().to_lowercase().contains(needle),
+            Some(Pattern::Regex(re)) => re.is_match(&line.display_text()),
+        };
+        hit != self.exclude
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.expression.is_empty() {
+            parts.push(self.expression.clone());
+        }
+        if self.stream != StreamFilter::All {
+            parts.push(format!("stream={}", self.stream.label()));
+        }
+        parts.join(" ")
+    }
+}
+
+/// Byte ranges of every case-insensitive occurrence of `query` in `line`.
+pub fn find_matches(line: &str, query: &str) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let needle: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let mut j = 0;
+        let mut k = i;
+        while j < needle.len() && k < chars.len() {
+            let mut lower = chars[k].1.to_lowercase();
+            if lower.len() != 1 || lower.next() != Some(needle[j]) {
+                break;
+            }
+            j += 1;
+            k += 1;
+        }
+        if j == needle.len() {
+            let end = chars.get(k).map(|(b, _)| *b).unwrap_or(line.len());
+            matches.push(chars[i].0..end);
+            i = k;
+        } else {
+            i += 1;
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::LogLevel;
+
+    fn line(stream: LogStream, text: &str) -> LogLine {
+        LogLine::new("web", stream, text)
+    }
+
+    fn filter(expression: &str) -> LogFilter {
+        LogFilter::parse(expression, StreamFilter::All).expect("valid filter")
+    }
+
+    #[test]
+    fn parses_field_conditions_apart_from_the_pattern() {
+        let f = filter("level>=warn req.id=\"abc\" timeout");
+        let conditions: Vec<(&str, CmpOp, &str)> =
+            f.conditions.iter().map(|c| (c.key.as_str(), c.op, c.value.as_str())).collect();
+        assert_eq!(conditions, [("level", CmpOp::Ge, "warn"), ("req.id", CmpOp::Eq, "abc")]);
+        assert!(matches!(&f.pattern, Some(Pattern::Substring(s)) if s == "timeout"));
+        assert!(!f.exclude);
+    }
+
+    #[test]
+    fn parses_every_operator() {
+        for (term, op) in [
+            ("a=1", CmpOp::Eq),
+            ("a!=1", CmpOp::Ne),
+            ("a>=1", CmpOp::Ge),
+            ("a<=1", CmpOp::Le),
+            ("a>1", CmpOp::Gt),
+            ("a<1", CmpOp::Lt),
+        ] {
+            assert_eq!(FieldCondition::parse(term).map(|c| c.op), Some(op), "{}", term);
+        }
+        assert!(FieldCondition::parse("=1").is_none());
+        assert!(FieldCondition::parse("a b=1").is_none());
+        assert!(FieldCondition::parse("plain").is_none());
+    }
+
+    #[test]
+    fn regex_and_exclusion() {
+        let f = filter("!/err(or)?\\b/");
+        assert!(f.exclude);
+        assert!(f.conditions.is_empty());
+        assert!(!f.matches(&line(LogStream::Stdout, "an ERROR happened")));
+        assert!(f.matches(&line(LogStream::Stdout, "all good")));
+        assert!(LogFilter::parse("/(/", StreamFilter::All).is_err());
+    }
+
+    #[test]
+    fn empty_patterns_are_rejected() {
+        for expression in ["//", "!//", "!", " ! "] {
+            assert!(LogFilter::parse(expression, StreamFilter::All).is_err(), "{}", expression);
+        }
+        assert!(filter("level>=warn").pattern.is_none());
+    }
+
+    #[test]
+    fn patterns_match_the_displayed_text() {
+        let json = line(LogStream::Stdout, r#"{"level":"info","msg":"ready","port":3000}"#);
+        assert!(filter("ready port").matches(&json));
+        assert!(filter("/^ready/").matches(&json));
+        // Raw JSON syntax is not what the user sees, so it does not match.
+        assert!(!filter("\"msg\"").matches(&json));
+    }
+
+    #[test]
+    fn substring_is_case_insensitive() {
+        let f = filter("Timeout");
+        assert!(f.matches(&line(LogStream::Stdout, "request TIMEOUT after 5s")));
+        assert!(!f.matches(&line(LogStream::Stdout, "request done")));
+    }
+
+    #[test]
+    fn level_conditions_compare_severities() {
+        let f = filter("level>=warn");
+        assert!(f.matches(&line(LogStream::Stdout, "ERROR disk full")));
+        assert!(f.matches(&line(LogStream::Stdout, r#"{"level":"warn","msg":"slow"}"#)));
+        assert!(!f.matches(&line(LogStream::Stdout, "INFO listening")));
+        // Lines without a level only pass `!=`.
+        assert!(!f.matches(&line(LogStream::Stdout, "no level here")));
+        assert!(filter("level!=info").matches(&line(LogStream::Stdout, "no level here")));
+        assert_eq!(line(LogStream::Stdout, "ERROR disk full").level, Some(LogLevel::Error));
+    }
+
+    #[test]
+    fn field_conditions_compare_numbers_numerically() {
+        let slow = line(LogStream::Stdout, "msg=done duration=250");
+        let fast = line(LogStream::Stdout, "msg=done duration=90");
+        let f = filter("duration>100");
+        assert!(f.matches(&slow));
+        assert!(!f.matches(&fast));
+        assert!(filter("msg=DONE").matches(&fast));
+        assert!(!filter("missing=1").matches(&fast));
+    }
+
+    #[test]
+    fn stream_filter_applies_first() {
+        let f = LogFilter::parse("", StreamFilter::Stderr).expect("valid filter");
+        assert!(f.is_active());
+        assert!(f.matches(&line(LogStream::Stderr, "x")));
+        assert!(!f.matches(&line(LogStream::Stdout, "x")));
+        assert_eq!(f.describe(), "stream=stderr");
+        assert!(!filter("  ").is_active());
+    }
+
+    #[test]
+    fn find_matches_returns_byte_ranges_ignoring_case() {
+        assert_eq!(find_matches("Error: error", "ERROR"), [0..5, 7..12]);
+        assert_eq!(find_matches("naïve NAÏVE", "naïve"), [0..6, 7..13]);
+        assert!(find_matches("abc", "").is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// UI state remembered per project in `.devrunner/state.json`.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct UiState {
+    /// Services pinned to the split log panes, in grid order.
+    #[serde(default)]
+    pub panes: Vec<String>,
+    #[serde(default)]
+    pub focused_pane: usize,
+    /// Sidebar and resources column widths in percent, once resized.
+    #[serde(default)]
+    pub columns: Option<[u16; 2]>,
+    /// Services picked in the finder, most recent first.
+    #[serde(default)]
+    pub recent_services: Vec<String>,
+    /// Commands run from the palette, most recent first.
+    #[serde(default)]
+    pub recent_commands: Vec<String>,
+    /// Collapsed sidebar groups.
+    #[serde(default)]
+    pub collapsed: Vec<String>,
+}
+
+pub fn state_path(root_dir: &Path) -> PathBuf {
+    root_dir.join(".devrunner").join("state.json")
+}
+
+impl UiState {
+    /// Load the saved state, falling back to defaults if it is missing or unreadable.
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_round_trips_through_the_file() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = state_path(dir.path());
+        let state = UiState {
+            panes: vec!["web: dev".to_string(), "api: dev".to_string()],
+            focused_pane: 1,
+            columns: Some([30, 20]),
+            recent_services: vec!["api: dev".to_string()],
+            recent_commands: vec!["start_all".to_string()],
+            collapsed: vec!["project:apps#apps".to_string()],
+        };
+        state.save(&path).expect("save");
+        let loaded = UiState::load(&path);
+        assert_eq!(loaded.panes, state.panes);
+        assert_eq!(loaded.focused_pane, 1);
+        assert_eq!(loaded.columns, Some([30, 20]));
+        assert_eq!(loaded.recent_services, state.recent_services);
+        assert_eq!(loaded.recent_commands, state.recent_commands);
+        assert_eq!(loaded.collapsed, state.collapsed);
+    }
+
+    #[test]
+    fn missing_fields_and_broken_files_fall_back_to_defaults() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("state.json");
+        assert!(UiState::load(&path).panes.is_empty());
+        fs::write(&path, r#"{"panes": ["web: dev"]}"#).expect("write");
+        let partial = UiState::load(&path);
+        assert_eq!(partial.panes, ["web: dev"]);
+        assert_eq!(partial.columns, None);
+        fs::write(&path, "{ not json").expect("write");
+        assert!(UiState::load(&path).panes.is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+use crate::models::{Service, ServiceStatus};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+/// One row of the sidebar tree.
+#[derive(Debug, Clone)]
+pub enum SidebarRow {
+    /// A directory or a project (manifest) grouping the services below it.
+    Group {
+        /// Stable identifier used to remember collapsed groups.
+        key: String,
+        label: String,
+        depth: usize,
+        /// Indices of every service in the subtree.
+        members: Vec<usize>,
+        collapsed: bool,
+    },
+    Service {
+        index: usize,
+        label: String,
+        depth: usize,
+    },
+}
+
+impl SidebarRow {
+    pub fn depth(&self) -> usize {
+        match self {
+            SidebarRow::Group { depth, .. } | SidebarRow::Service { depth, .. } => *depth,
+        }
+    }
+}
+
+/// Aggregated status of a group, e.g. `2/5 running, 1 failed`.
+pub fn group_summary(services: &[Service], members: &[usize]) -> String {
+    let running = members
+        .iter()
+        .filter(|&&i| matches!(services[i].status, ServiceStatus::Running(_)))
+        .count();
+    let failed = members.iter().filter(|&&i| services[i].status == ServiceStatus::Failed).count();
+    let mut summary = format!("{}/{} running", running, members.len());
+    if failed > 0 {
+        summary.push_str(&format!(", {} failed", failed));
+    }
+    summary
+}
+
+#[derive(Default)]
+struct Dir {
+    children: BTreeMap<String, Dir>,
+    /// Services of each manifest found directly in this directory.
+    projects: BTreeMap<String, Vec<usize>>,
+}
+
+impl Dir {
+    fn members(&self) -> Vec<usize> {
+        let mut members: Vec<usize> = self.projects.values().flatten().copied().collect();
+        for child in self.children.values() {
+            members.extend(child.members());
+        }
+        members
+    }
+}
+
+/// `web: dev` splits into the package `web` and the script `dev`.
+pub fn split_name(name: &str) -> (&str, &str) {
+    name.split_once(": ").unwrap_or((name, name))
+}
+
+/// Every row of the fully expanded tree, each paired with the keys of the
+/// groups above it.
+fn full_rows(services: &[Service], root: &Path) -> Vec<(SidebarRow, Vec<String>)> {
+    let mut tree = Dir::default();
+    for (i, service) in services.iter().enumerate() {
+        let rel = service.path.strip_prefix(root).unwrap_or(&service.path);
+        let mut dir = &mut tree;
+        for part in rel.components() {
+            dir = dir.children.entry(part.as_os_str().to_string_lossy().into_owned()).or_default();
+        }
+        let (package, _) = split_name(&service.name);
+        let project = format!("{} ({:?})", package, service.project_type);
+        dir.projects.entry(project).or_default().push(i);
+    }
+    let mut rows = Vec::new();
+    flatten(services, &tree, "", 0, &mut Vec::new(), &mut rows);
+    rows
+}
+
+fn flatten(
+    services: &[Service],
+    dir: &Dir,
+    path: &str,
+    depth: usize,
+    ancestors: &mut Vec<String>,
+    rows: &mut Vec<(SidebarRow, Vec<String>)>,
+) {
+    for (label, members) in &dir.projects {
+        // The label names the package, so two packages in one directory
+        // collapse separately.
+        let key = format!("project:{}#{}", path, label);
+        rows.push((
+            SidebarRow::Group { key: key.clone(), label: label.clone(), depth, members: members.clone(), collapsed: false },
+            ancestors.clone(),
+        ));
+        ancestors.push(key);
+        for &index in members {
+            let (_, script) = split_name(&services[index].name);
+            rows.push((SidebarRow::Service { index, label: script.to_string(), depth: depth + 1 }, ancestors.clone()));
+        }
+        ancestors.pop();
+    }
+
+    for (name, child) in &dir.children {
+        // Fold chains of directories holding nothing but one subdirectory
+        // into a single `apps/web` row.
+        let mut name = name.clone();
+        let mut child = child;
+        while child.projects.is_empty() && child.children.len() == 1 {
+            let (next_name, next) = child.children.iter().next().expect("one child");
+            name = format!("{}/{}", name, next_name);
+            child = next;
+        }
+        let child_path = if path.is_empty() { name.clone() } else { format!("{}/{}", path, name) };
+        // A directory holding a single project needs no row of its own.
+        if child.children.is_empty() && child.projects.len() == 1 {
+            flatten(services, child, &child_path, depth, ancestors, rows);
+            continue;
+        }
+        let key = format!("dir:{}", child_path);
+        rows.push((
+            SidebarRow::Group { key: key.clone(), label: format!("{}/", name), depth, members: child.members(), collapsed: false },
+            ancestors.clone(),
+        ));
+        ancestors.push(key);
+        flatten(services, child, &child_path, depth + 1, ancestors, rows);
+        ancestors.pop();
+    }
+}
+
+/// The rows currently shown: children of collapsed groups are left out.
+pub fn visible_rows(services: &[Service], root: &Path, collapsed: &HashSet<String>) -> Vec<SidebarRow> {
+    full_rows(services, root)
+        .into_iter()
+        .filter(|(_, ancestors)| !ancestors.iter().any(|key| collapsed.contains(key)))
+        .map(|(mut row, _)| {
+            if let SidebarRow::Group { key, collapsed: is_collapsed, .. } = &mut row {
+                *is_collapsed = collapsed.contains(key);
+            }
+            row
+        })
+        .collect()
+}
+
+/// Keys of the groups containing a service, outermost first.
+pub fn ancestors_of(services: &[Service], root: &Path, index: usize) -> Vec<String> {
+    full_rows(services, root)
+        .into_iter()
+        .find(|(row, _)| matches!(row, SidebarRow::Service { index: i, .. } if *i == index))
+        .map(|(_, ancestors)| ancestors)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProjectType;
+
+    fn services() -> Vec<Service> {
+        [
+            ("root: dev", ""),
+            ("web: dev", "apps/web"),
+            ("web: build", "apps/web"),
+            ("api: dev", "apps/api"),
+            ("lib: test", "packages/deep/lib"),
+        ]
+        .into_iter()
+        .map(|(name, dir)| {
+            let path = Path::new("/repo").join(dir);
+            Service::new(name.to_string(), path.clone(), path.join("package.json"), ProjectType::Node, String::new())
+        })
+        .collect()
+    }
+
+    /// Rows as indented labels, with a `+` on collapsed groups.
+    fn render(rows: &[SidebarRow]) -> Vec<String> {
+        rows.iter()
+            .map(|row| {
+                let indent = "  ".repeat(row.depth());
+                match row {
+                    SidebarRow::Group { label, collapsed, .. } => format!("{}{}{}", indent, label, if *collapsed { " +" } else { "" }),
+                    SidebarRow::Service { label, .. } => format!("{}{}", indent, label),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn services_are_grouped_by_directory_and_project() {
+        let rows = visible_rows(&services(), Path::new("/repo"), &HashSet::new());
+        assert_eq!(
+            render(&rows),
+            [
+                "root (Node)",
+                "  dev",
+                "apps/",
+                "  api (Node)",
+                "    dev",
+                "  web (Node)",
+                "    dev",
+                "    build",
+                // Single-child directories fold into the project row.
+                "lib (Node)",
+                "  test",
+            ]
+        );
+        let SidebarRow::Group { members, .. } = &rows[2] else {
+            panic!("apps/ is a group");
+        };
+        assert_eq!(members, &[3, 1, 2]);
+    }
+
+    #[test]
+    fn collapsed_groups_hide_their_subtree() {
+        let collapsed = HashSet::from(["dir:apps".to_string(), "project:packages/deep/lib#lib (Node)".to_string()]);
+        let rows = visible_rows(&services(), Path::new("/repo"), &collapsed);
+        assert_eq!(render(&rows), ["root (Node)", "  dev", "apps/ +", "lib (Node) +"]);
+    }
+
+    #[test]
+    fn ancestors_are_listed_outermost_first() {
+        assert_eq!(ancestors_of(&services(), Path::new("/repo"), 2), ["dir:apps", "project:apps/web#web (Node)"]);
+        assert_eq!(ancestors_of(&services(), Path::new("/repo"), 0), ["project:#root (Node)"]);
+    }
+
+    #[test]
+    fn group_summary_counts_running_and_failed() {
+        let mut services = services();
+        services[1].status = ServiceStatus::Running(10);
+        services[2].status = ServiceStatus::Failed;
+        assert_eq!(group_summary(&services, &[1, 2, 3]), "1/3 running, 1 failed");
+        assert_eq!(group_summary(&services, &[0]), "0/1 running");
+        assert_eq!(split_name("web: dev"), ("web", "dev"));
+        assert_eq!(split_name("custom"), ("custom", "custom"));
+    }
+}
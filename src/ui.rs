@@ -1,15 +1,130 @@
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, BorderType, Borders, List, ListItem, Paragraph, Wrap, Sparkline},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph, Sparkline, Wrap},
     Frame,
 };
 use tui_big_text::{BigText, PixelSize};
-use crate::models::{Service, ServiceStatus};
+use unicode_width::UnicodeWidthChar;
+use crate::app::{active_source, describe_holder, App, InputMode, LogView, Popup, ScreenLayout, Selection, SortMode};
+use crate::buffer::LogBuffer;
+use crate::logparse::Record;
+use crate::keys::{Action, Context, Keymap};
+use crate::palette::{Candidate, Palette, PaletteKind};
+use crate::reload::ChangeKind;
+use crate::tree::{self, SidebarRow};
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::search::{self, LogFilter, StreamFilter};
+use std::ops::Range;
+use crate::models::{LogLevel, LogLine, LogStream, ServiceStatus};
 use sysinfo::System;
 
-pub fn draw(f: &mut Frame, services: &[Service], selected_index: usize, app_title: &str, cpu_history: &[u64], sys: &mut System) {
+/// Split `text` into rows no wider than `width` terminal cells.
+///
+/// Wrapping is done here rather than by `Paragraph` so that scroll offsets
+/// computed by the log pane match exactly what ends up on screen.
+fn wrap_rows(text: &str, width: usize) -> Vec<Range<usize>> {
+    let mut rows = Vec::new();
+    if width == 0 || text.is_empty() {
+        rows.push(0..text.len());
+        return rows;
+    }
+    let mut start = 0;
+    let mut used = 0;
+    for (idx, ch) in text.char_indices() {
+        let w = ch.width().unwrap_or(0);
+        if used + w > width && idx > start {
+            rows.push(start..idx);
+            start = idx;
+            used = 0;
+        }
+        used += w;
+    }
+    rows.push(start..text.len());
+    rows
+}
+
+/// Render one wrapped row of a log line: the first `label.0` bytes use the
+/// label style, search matches are highlighted, and the rest uses `base`.
+fn highlight_row(
+    text: &str,
+    row: Range<usize>,
+    base: Style,
+    label: (usize, Style),
+    matches: &[Range<usize>],
+    current: bool,
+) -> Line<'static> {
+    let match_style = if current {
+        Style::default().fg(Color::Black).bg(Color::LightYellow).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::Black).bg(Color::Yellow)
+    };
+    let mut points = vec![row.start, row.end, label.0];
+    for m in matches {
+        points.push(m.start);
+        points.push(m.end);
+    }
+    points.retain(|p| (row.start..=row.end).contains(p));
+    points.sort_unstable();
+    points.dedup();
+
+    let spans: Vec<Span> = points
+        .windows(2)
+        .map(|w| {
+            let (start, end) = (w[0], w[1]);
+            let style = if matches.iter().any(|m| m.start <= start && end <= m.end) {
+                match_style
+            } else if end <= label.0 {
+                label.1
+            } else {
+                base
+            };
+            Span::styled(text[start..end].to_string(), style)
+        })
+        .collect();
+    Line::from(spans)
+}
+
+/// Format a log line for display, returning the text and the byte length of
+/// the timestamp/label prefix preceding the original output.
+fn format_log_line(line: &LogLine) -> (String, usize) {
+    let stream = match line.stream {
+        LogStream::Stdout => "out",
+        LogStream::Stderr => "err",
+        LogStream::System => "sys",
+    };
+    let level = line.level.map(LogLevel::as_str).unwrap_or("");
+    let time = line.record.as_ref().and_then(Record::timestamp).unwrap_or(line.timestamp);
+    let prefix = format!("{} {} {:<5} ", time.format("%H:%M:%S"), stream, level);
+    let prefix_len = prefix.len();
+    (prefix + &line.display_text(), prefix_len)
+}
+
+fn log_style(line: &LogLine, text_color: Color) -> Style {
+    match line.level {
+        Some(LogLevel::Error) => Style::default().fg(Color::Red),
+        Some(LogLevel::Warn) => Style::default().fg(Color::Yellow),
+        Some(LogLevel::Info) if line.stream == LogStream::System => Style::default().fg(Color::Green),
+        Some(LogLevel::Debug | LogLevel::Trace) => Style::default().fg(Color::DarkGray),
+        _ => Style::default().fg(text_color),
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1}MiB", bytes as f64 / 1024.0 / 1024.0)
+    } else {
+        format!("{:.1}KiB", bytes as f64 / 1024.0)
+    }
+}
+
+pub fn draw(f: &mut Frame, app: &mut App, sys: &mut System) {
+    let services = &app.services;
+    let selected_index = app.selected_index;
+    let app_title = app.title.as_str();
+    let cpu_history = app.cpu_history.as_slice();
+
     // Refresh system info
     // Optimization: System info is refreshed in the tick handler in app.rs
     // sys.refresh_all();
@@ -52,40 +167,96 @@ pub fn draw(f: &mut Frame, services: &[Service], selected_index: usize, app_titl
     
     f.render_widget(title_line, header_area);
 
-    // 2. Main Content (3 Columns)
+    // 2. Main Content (3 Columns, resizable by dragging their borders)
+    let [sidebar_width, resources_width] = app.columns;
     let main_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Percentage(25), // Services
-            Constraint::Percentage(50), // Logs
-            Constraint::Percentage(25), // Resources
+            Constraint::Percentage(sidebar_width), // Services
+            Constraint::Percentage(100 - sidebar_width - resources_width), // Logs
+            Constraint::Percentage(resources_width), // Resources
         ])
         .split(chunks[1]);
 
     // --- LEFT COLUMN: SERVICES ---
-    let items: Vec<ListItem> = services
+    let rows = app.sidebar_rows();
+    let cursor = app.cursor_row(&rows);
+    let items: Vec<ListItem> = rows
         .iter()
         .enumerate()
-        .map(|(i, s)| {
-            let is_selected = i == selected_index;
-            
-            // Symbols matching the image roughly
-            let (status_symbol, color) = match s.status {
-                ServiceStatus::Running(_) => ("[●]", primary_color),
-                ServiceStatus::Failed => ("[✖]", Color::Red),
-                ServiceStatus::Stopped => ("[ ]", dimmed_color),
-                ServiceStatus::Completed => ("[✔]", Color::Green),
-            };
-
+        .map(|(row, entry)| {
+            let is_selected = cursor == Some(row);
             let bg_color = if is_selected { highlight_color } else { Color::Reset };
             let fg_color = if is_selected { Color::Black } else { text_color }; // Black text on Cyan highlight
+            let indent = "  ".repeat(entry.depth());
 
-            let line = Line::from(vec![
-                Span::styled(format!("{} ", status_symbol), Style::default().fg(if is_selected { Color::Black } else { color })),
-                Span::raw(&s.name),
-            ]);
+            let spans = match entry {
+                SidebarRow::Group { label, members, collapsed, .. } => {
+                    let arrow = if *collapsed { "▸" } else { "▾" };
+                    let failed = members.iter().any(|&i| services[i].status == ServiceStatus::Failed);
+                    let summary_color = match () {
+                        _ if is_selected => Color::Black,
+                        _ if failed => Color::Red,
+                        _ => dimmed_color,
+                    };
+                    vec![
+                        Span::raw(format!("{}{} ", indent, arrow)),
+                        Span::styled(label.clone(), Style::default().add_modifier(Modifier::BOLD)),
+                        Span::styled(format!(" {}", tree::group_summary(services, members)), Style::default().fg(summary_color)),
+                    ]
+                }
+                SidebarRow::Service { index, label, .. } => {
+                    let s = &services[*index];
+                    // Alerting services blink between their normal colors and red.
+                    let blink = s.metrics.alerting && app.ticks.is_multiple_of(2);
+                    // Symbols matching the image roughly
+                    let (status_symbol, color) = match s.status {
+                        ServiceStatus::Running(_) => ("[●]", primary_color),
+                        ServiceStatus::Failed => ("[✖]", Color::Red),
+                        ServiceStatus::Stopped => ("[ ]", dimmed_color),
+                        ServiceStatus::Completed => ("[✔]", Color::Green),
+                    };
+                    let mut spans = vec![
+                        Span::raw(indent),
+                        Span::styled(format!("{} ", status_symbol), Style::default().fg(if is_selected { Color::Black } else { color })),
+                        Span::raw(label.clone()),
+                    ];
+                    if let Some(usage) = s.metrics.current {
+                        spans.push(Span::styled(
+                            format!(" {:.0}% {}", usage.cpu, format_bytes(usage.rss as usize)),
+                            Style::default().fg(if is_selected { Color::Black } else { dimmed_color }),
+                        ));
+                    }
+                    if !s.metrics.ports.is_empty() {
+                        let ports: Vec<String> = s.metrics.ports.iter().map(|l| l.to_string()).collect();
+                        spans.push(Span::styled(
+                            format!(" {}", ports.join(" ")),
+                            Style::default().fg(if is_selected { Color::Black } else { Color::Cyan }),
+                        ));
+                    }
+                    if s.stale {
+                        spans.push(Span::styled(" (removed)", Style::default().fg(if is_selected { Color::Black } else { dimmed_color })));
+                    }
+                    if app.watching.contains(&s.name) {
+                        spans.push(Span::styled(" ⟳", Style::default().fg(if is_selected { Color::Black } else { dimmed_color })));
+                    }
+                    if blink {
+                        for span in &mut spans {
+                            span.style = span.style.fg(Color::White).bg(Color::Red);
+                        }
+                    }
+                    if app.show_all {
+                        if app.muted.contains(&s.name) {
+                            spans.push(Span::styled(" (muted)", Style::default().fg(dimmed_color)));
+                        } else {
+                            spans.push(Span::styled(" ■", Style::default().fg(service_color(*index))));
+                        }
+                    }
+                    spans
+                }
+            };
 
-            ListItem::new(line).style(Style::default().bg(bg_color).fg(fg_color))
+            ListItem::new(Line::from(spans)).style(Style::default().bg(bg_color).fg(fg_color))
         })
         .collect();
 
@@ -94,46 +265,99 @@ pub fn draw(f: &mut Frame, services: &[Service], selected_index: usize, app_titl
         .border_type(BorderType::Rounded)
         .border_style(Style::default().fg(primary_color))
         .title(" SERVICES ");
+    let sidebar_block = if app.sort == SortMode::Tree {
+        sidebar_block
+    } else {
+        sidebar_block.title(Line::styled(format!(" {} ", app.sort.label()), Style::default().fg(Color::Yellow)).right_aligned())
+    };
 
     let list = List::new(items).block(sidebar_block);
-    f.render_widget(list, main_chunks[0]);
+    let mut sidebar_state = ListState::default().with_selected(cursor);
+    f.render_stateful_widget(list, main_chunks[0], &mut sidebar_state);
 
     // --- CENTER COLUMN: LOGS ---
     let selected_service = services.get(selected_index);
-    let log_block = Block::default()
-        .borders(Borders::ALL)
-        .border_type(BorderType::Rounded)
-        .border_style(Style::default().fg(primary_color))
-        .title(" LOGS ");
+    let service_colors: HashMap<String, Color> = services
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s.name.clone(), service_color(i)))
+        .collect();
+    let split = !app.panes.is_empty();
+    let pane_areas = pane_grid(main_chunks[1], app.panes.len().max(1));
+    let mut pane_screens = Vec::with_capacity(pane_areas.len());
+    let empty_hint = format!("No output yet. Press [{}] to start.", app.keymap.describe(Action::Start));
 
-    if let Some(service) = selected_service {
-        let logs: Vec<Line> = service.logs
-            .iter()
-            .rev()
-            .take(50)
-            .rev()
-            .map(|s| {
-                if s.contains("[INFO]") {
-                    Line::styled(s.as_str(), Style::default().fg(Color::Green))
-                } else if s.contains("[ERROR]") {
-                    Line::styled(s.as_str(), Style::default().fg(Color::Red))
-                } else if s.contains("[EXEC]") {
-                    Line::styled(s.as_str(), Style::default().fg(Color::Yellow))
-                } else {
-                    Line::styled(s.as_str(), Style::default().fg(text_color))
-                }
-            })
-            .collect();
-            
-        let paragraph = Paragraph::new(logs)
-            .block(log_block)
-            .wrap(Wrap { trim: false });
-            
-        f.render_widget(paragraph, main_chunks[1]);
-    } else {
-        f.render_widget(Block::default().borders(Borders::ALL).title(" LOGS "), main_chunks[1]);
+    for (pane, area) in pane_areas.iter().enumerate() {
+        let focused = !split || pane == app.focused_pane;
+        let border_color = if focused { primary_color } else { dimmed_color };
+        let mut log_block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(border_color));
+
+        // The focused pane shows the selection (including the merged and
+        // archive views); other panes show their pinned service live.
+        let source = if focused {
+            let merged = app.show_all.then_some(&app.merged_logs);
+            active_source(services, selected_index, &app.archive_view, merged)
+        } else {
+            services
+                .iter()
+                .find(|s| s.name == app.panes[pane])
+                .map(|s| (s.name.clone(), &s.logs))
+        };
+        let Some((key, buffer)) = source else {
+            pane_screens.push((String::new(), log_block.inner(*area)));
+            f.render_widget(log_block.title(" LOGS "), *area);
+            continue;
+        };
+        pane_screens.push((key.clone(), log_block.inner(*area)));
+
+        log_block = if split {
+            log_block.title(format!(" LOGS: {} ", if focused && app.show_all { "all" } else { key.as_str() }))
+        } else {
+            log_block.title(" LOGS ")
+        };
+        let merged_view = focused && app.show_all;
+        if merged_view {
+            log_block = log_block.title(Line::styled(
+                format!(" ALL SERVICES ({} muted) [{}] Back ", app.muted.len(), app.keymap.describe(Action::ToggleAll)),
+                Style::default().fg(Color::Black).bg(primary_color),
+            ));
+        } else if focused
+            && let Some(archived) = &app.archive_view
+            && archived.service == services[selected_index].name
+        {
+            log_block = log_block.title(Line::styled(
+                format!(" archive: {} [{}] Close ", archived.file_name, app.keymap.describe(Action::Cancel)),
+                Style::default().fg(Color::Black).bg(Color::Magenta),
+            ));
+        }
+        let ctx = PaneContext {
+            search: &app.search,
+            text_color,
+            dimmed_color,
+            service_colors: merged_view.then_some(&service_colors),
+            muted: merged_view.then_some(&app.muted),
+            empty_hint: &empty_hint,
+        };
+        let view = app.log_views.entry(key).or_default();
+        draw_log_pane(f, *area, log_block, buffer, view, &ctx);
     }
 
+    if let Some(selection) = &app.selection
+        && let Some((_, area)) = pane_screens.get(selection.pane)
+    {
+        highlight_selection(f, *area, selection);
+    }
+    app.screen = ScreenLayout {
+        main: chunks[1],
+        sidebar: main_chunks[0],
+        resources: main_chunks[2],
+        panes: pane_screens,
+        sidebar_offset: sidebar_state.offset(),
+    };
+
     // --- RIGHT COLUMN: RESOURCES ---
     let resources_block = Block::default()
         .borders(Borders::ALL)
@@ -148,9 +372,13 @@ pub fn draw(f: &mut Frame, services: &[Service], selected_index: usize, app_titl
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(1), // CPU Label
-            Constraint::Length(10), // Graph
+            Constraint::Length(5), // Graph
             Constraint::Length(1), // Spacer
-            Constraint::Length(4), // Memory details
+            Constraint::Length(1), // Service CPU label
+            Constraint::Length(3), // Service CPU graph
+            Constraint::Length(1), // Service memory label
+            Constraint::Length(3), // Service memory graph
+            Constraint::Min(4), // Memory details
         ])
         .split(resources_area);
 
@@ -169,8 +397,40 @@ pub fn draw(f: &mut Frame, services: &[Service], selected_index: usize, app_titl
         .style(Style::default().fg(graph_color));
     f.render_widget(sparkline, resource_chunks[1]);
 
+    // Selected service's process tree
+    if let Some(service) = selected_service {
+        let metrics = &service.metrics;
+        let usage = metrics.current;
+        let cpu_label = match usage {
+            Some(usage) => format!("{:.0}%", usage.cpu),
+            None => "-".to_string(),
+        };
+        let rss_label = match usage {
+            Some(usage) => format_bytes(usage.rss as usize),
+            None => "-".to_string(),
+        };
+        let cpu_data: Vec<u64> = metrics.cpu_history.iter().copied().collect();
+        let rss_data: Vec<u64> = metrics.rss_history.iter().copied().collect();
+        f.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::raw(format!("{} CPU ", service.name)),
+                Span::styled(cpu_label, Style::default().add_modifier(Modifier::BOLD)),
+            ])),
+            resource_chunks[3],
+        );
+        f.render_widget(Sparkline::default().data(&cpu_data).style(Style::default().fg(graph_color)), resource_chunks[4]);
+        f.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::raw("RSS "),
+                Span::styled(rss_label, Style::default().add_modifier(Modifier::BOLD)),
+            ])),
+            resource_chunks[5],
+        );
+        f.render_widget(Sparkline::default().data(&rss_data).style(Style::default().fg(primary_color)), resource_chunks[6]);
+    }
+
     // Memory Details
-    let mem_text = vec![
+    let mut mem_text = vec![
         Line::from("Memory"),
         Line::from(vec![
             Span::styled(format!("{:.1}MiB", used_mem as f64 / 1024.0 / 1024.0), Style::default().fg(text_color)),
@@ -179,13 +439,680 @@ pub fn draw(f: &mut Frame, services: &[Service], selected_index: usize, app_titl
             Span::raw(format!("{}/{}GB", used_mem / 1024 / 1024 / 1024, total_mem / 1024 / 1024 / 1024)),
         ]),
     ];
+    if let Some(service) = selected_service {
+        if let Some(usage) = service.metrics.current {
+            mem_text.push(Line::from(""));
+            mem_text.push(Line::from(format!(
+                "{} procs, {} threads, {} fds",
+                usage.processes, usage.threads, usage.fds
+            )));
+        }
+        mem_text.push(Line::from(""));
+        mem_text.push(Line::from("Log buffer"));
+        mem_text.push(Line::from(format!(
+            "{} lines, {}",
+            service.logs.len(),
+            format_bytes(service.logs.bytes())
+        )));
+        if service.logs.dropped() > 0 {
+            mem_text.push(Line::styled(
+                format!("{} dropped", service.logs.dropped()),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+    }
     let mem_paragraph = Paragraph::new(mem_text);
-    f.render_widget(mem_paragraph, resource_chunks[3]);
+    f.render_widget(mem_paragraph, resource_chunks[7]);
 
 
     // 3. Footer (Simple help line)
-    let footer_text = "[Q] Quit | [S/Enter] Start | [J/K] Move | [H] Help";
+    let footer_text = match app.input_mode {
+        InputMode::Search => format!("/{}█", app.input),
+        InputMode::Filter => format!("Filter ([!]text, [!]/regex/, key=value, level>=warn): {}█", app.input),
+        InputMode::Normal => match &app.message {
+            Some(message) => message.clone(),
+            None => FOOTER_ACTIONS
+                .iter()
+                .map(|action| format!("[{}] {}", app.keymap.describe(*action), action.label()))
+                .collect::<Vec<_>>()
+                .join(" | "),
+        },
+    };
     let footer = Paragraph::new(footer_text)
         .style(Style::default().fg(text_color).bg(Color::Black));
     f.render_widget(footer, chunks[2]);
+
+    if let Some(popup) = &app.popup {
+        draw_popup(f, popup, &app.keymap);
+    }
+}
+
+/// Colors cycled through to tell services apart in merged views.
+const SERVICE_COLORS: [Color; 8] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightMagenta,
+];
+
+pub fn service_color(index: usize) -> Color {
+    SERVICE_COLORS[index % SERVICE_COLORS.len()]
+}
+
+/// Per-frame inputs shared by every log pane.
+struct PaneContext<'a> {
+    search: &'a str,
+    text_color: Color,
+    dimmed_color: Color,
+    /// Set for merged views: each line is prefixed with its service name in
+    /// that service's color.
+    service_colors: Option<&'a HashMap<String, Color>>,
+    /// Services hidden from a merged view.
+    muted: Option<&'a HashSet<String>>,
+    /// Shown in place of logs while the buffer is empty.
+    empty_hint: &'a str,
+}
+
+/// A log line formatted for display and wrapped to the pane width.
+struct DisplayLine {
+    seq: u64,
+    text: String,
+    prefix_len: usize,
+    label: (usize, Style),
+    style: Style,
+    rows: Vec<Range<usize>>,
+}
+
+/// Whether a pane with `filter` shows `line`.
+fn shown(line: &LogLine, filter: &LogFilter, ctx: &PaneContext) -> bool {
+    filter.matches(line) && !ctx.muted.is_some_and(|m| m.contains(&line.service))
+}
+
+fn display_line(seq: u64, line: &LogLine, ctx: &PaneContext, width: usize) -> DisplayLine {
+    let (mut text, mut prefix_len) = format_log_line(line);
+    let mut label = (0, Style::default());
+    if let Some(colors) = ctx.service_colors {
+        let name = format!("{} │ ", line.service);
+        let color = colors.get(&line.service).copied().unwrap_or(ctx.text_color);
+        label = (name.len(), Style::default().fg(color).add_modifier(Modifier::BOLD));
+        prefix_len += name.len();
+        text = name + &text;
+    }
+    let rows = wrap_rows(&text, width);
+    DisplayLine { seq, text, prefix_len, label, style: log_style(line, ctx.text_color), rows }
+}
+
+/// What a `RowIndex` was built for; any change means rebuilding it.
+#[derive(Debug, Clone, PartialEq)]
+struct RowKey {
+    width: usize,
+    filter: (String, StreamFilter),
+    muted: Option<Vec<String>>,
+    labeled: bool,
+}
+
+/// Where each line a log pane shows starts, in wrapped rows. Kept between
+/// frames so that only lines pushed since the last draw are formatted and
+/// wrapped, instead of the whole buffer.
+#[derive(Debug, Clone, Default)]
+pub struct RowIndex {
+    key: Option<RowKey>,
+    /// Shown lines as `(seq, first row)`. Rows are counted from when the
+    /// index was built, so evicting a line doesn't renumber the rest.
+    lines: VecDeque<(u64, usize)>,
+    /// Row after the last line.
+    end: usize,
+    /// Sequence number of the next line to index.
+    next_seq: u64,
+}
+
+impl RowIndex {
+    /// Catch up with `buffer`: forget evicted lines and index new ones.
+    fn update(&mut self, buffer: &LogBuffer, filter: &LogFilter, ctx: &PaneContext, width: usize) {
+        let first_seq = buffer.end_seq() - buffer.len() as u64;
+        let key = RowKey {
+            width,
+            filter: (filter.expression.clone(), filter.stream),
+            muted: ctx.muted.map(|m| {
+                let mut muted: Vec<String> = m.iter().cloned().collect();
+                muted.sort();
+                muted
+            }),
+            labeled: ctx.service_colors.is_some(),
+        };
+        if self.key.as_ref() != Some(&key) || self.next_seq > buffer.end_seq() {
+            *self = RowIndex { key: Some(key), next_seq: first_seq, ..RowIndex::default() };
+        }
+        while self.lines.front().is_some_and(|(seq, _)| *seq < first_seq) {
+            self.lines.pop_front();
+        }
+        let start = self.next_seq.max(first_seq);
+        for (seq, line) in buffer.iter().skip((start - first_seq) as usize) {
+            if shown(line, filter, ctx) {
+                self.lines.push_back((seq, self.end));
+                self.end += display_line(seq, line, ctx, width).rows.len();
+            }
+        }
+        self.next_seq = buffer.end_seq();
+    }
+
+    fn base(&self) -> usize {
+        self.lines.front().map_or(self.end, |(_, row)| *row)
+    }
+
+    fn total(&self) -> usize {
+        self.end - self.base()
+    }
+
+    /// Row of the first shown line at or after `seq`.
+    fn row_of(&self, seq: u64) -> usize {
+        let i = self.lines.partition_point(|(s, _)| *s < seq);
+        self.lines.get(i).map_or(self.end, |(_, row)| *row) - self.base()
+    }
+
+    fn contains(&self, seq: u64) -> bool {
+        self.lines.binary_search_by_key(&seq, |(s, _)| *s).is_ok()
+    }
+
+    /// Position in `lines` of the line covering `row`, and the wrapped row
+    /// within it.
+    fn line_at(&self, row: usize) -> Option<(usize, usize)> {
+        let row = row + self.base();
+        let i = self.lines.partition_point(|(_, start)| *start <= row).checked_sub(1)?;
+        (row < self.end).then(|| (i, row - self.lines[i].1))
+    }
+}
+
+/// Render `buffer` into `area`, keeping `view`'s scroll state in sync with
+/// what ends up on screen.
+fn draw_log_pane(f: &mut Frame, area: Rect, mut block: Block, buffer: &LogBuffer, view: &mut LogView, ctx: &PaneContext) {
+    let inner = block.inner(area);
+    let width = inner.width as usize;
+    let height = inner.height as usize;
+
+    view.rows.update(buffer, &view.filter, ctx, width);
+    let total_rows = view.rows.total();
+    view.total_rows = total_rows;
+    view.page_height = height;
+    if let Some(target) = view.jump_to.take() {
+        view.top_row = view.rows.row_of(target).saturating_sub(height / 2);
+    } else if !view.follow
+        && view.top_row == view.drawn_top
+        && let Some((seq, sub)) = view.anchor
+    {
+        let still_present = view.rows.contains(seq);
+        view.top_row = view.rows.row_of(seq) + if still_present { sub } else { 0 };
+    }
+    let bottom = total_rows.saturating_sub(height);
+    if view.follow || view.top_row > bottom {
+        view.top_row = bottom;
+    }
+
+    // Only the lines on screen are formatted.
+    let mut display: Vec<DisplayLine> = Vec::new();
+    let mut skip = 0;
+    view.anchor = None;
+    view.last_visible = None;
+    if let Some((first, sub)) = view.rows.line_at(view.top_row) {
+        view.anchor = Some((view.rows.lines[first].0, sub));
+        skip = sub;
+        let mut rows = 0;
+        for &(seq, _) in view.rows.lines.range(first..) {
+            if rows >= skip + height {
+                break;
+            }
+            let Some(line) = buffer.get(seq) else {
+                break;
+            };
+            let d = display_line(seq, line, ctx, width);
+            rows += d.rows.len();
+            view.last_visible = Some(seq);
+            display.push(d);
+        }
+    }
+    view.drawn_top = view.top_row;
+
+    view.visible_rows = display
+        .iter()
+        .flat_map(|d| d.rows.iter().enumerate().map(|(i, row)| (d.text[row.clone()].to_string(), i > 0)))
+        .skip(skip)
+        .take(height)
+        .collect();
+
+    let search_query = ctx.search;
+    let cursor = view.cursor;
+    let mut logs: Vec<Line> = display
+        .iter()
+        .flat_map(|d| {
+            let style = d.style;
+            let matches: Vec<Range<usize>> = search::find_matches(&d.text[d.prefix_len..], search_query)
+                .into_iter()
+                .map(|m| m.start + d.prefix_len..m.end + d.prefix_len)
+                .collect();
+            let current = cursor == Some(d.seq);
+            d.rows.iter().map(move |row| highlight_row(&d.text, row.clone(), style, d.label, &matches, current))
+        })
+        .skip(skip)
+        .take(height)
+        .collect();
+
+    if buffer.is_empty() {
+        logs.push(Line::styled(ctx.empty_hint, Style::default().fg(ctx.dimmed_color)));
+    }
+    if buffer.dropped() > 0 {
+        block = block.title(Line::styled(
+            format!(" {} dropped ", buffer.dropped()),
+            Style::default().fg(ctx.dimmed_color),
+        ));
+    }
+    if view.filter.is_active() {
+        block = block.title(Line::styled(
+            format!(" filter: {} ", view.filter.describe()),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if !view.follow {
+        let indicator = if view.new_lines > 0 {
+            format!(" ▼ {} new lines [End] Follow ", view.new_lines)
+        } else {
+            " PAUSED [End] Follow ".to_string()
+        };
+        block = block.title_bottom(
+            Line::styled(indicator, Style::default().fg(Color::Black).bg(Color::Yellow)).right_aligned(),
+        );
+    }
+
+    let paragraph = Paragraph::new(logs).block(block);
+    f.render_widget(paragraph, area);
+}
+
+/// Draw the mouse selection in reverse video over an already rendered pane.
+fn highlight_selection(f: &mut Frame, area: Rect, selection: &Selection) {
+    let ((start_col, start_row), (end_col, end_row)) = selection.ordered();
+    let buf = f.buffer_mut();
+    for row in start_row..=end_row.min(area.height.saturating_sub(1)) {
+        let from = if row == start_row { start_col } else { 0 };
+        let to = if row == end_row { end_col } else { area.width.saturating_sub(1) };
+        for col in from..=to.min(area.width.saturating_sub(1)) {
+            if let Some(cell) = buf.cell_mut((area.x + col, area.y + row)) {
+                cell.modifier.insert(Modifier::REVERSED);
+            }
+        }
+    }
+}
+
+/// Split the log column into a grid for `count` panes: stacked for two,
+/// two on top and one below for three, and 2x2 for four.
+fn pane_grid(area: Rect, count: usize) -> Vec<Rect> {
+    if count <= 1 {
+        return vec![area];
+    }
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+    let halves = |row: Rect| {
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(row)
+    };
+    match count {
+        2 => vec![rows[0], rows[1]],
+        3 => {
+            let top = halves(rows[0]);
+            vec![top[0], top[1], rows[1]]
+        }
+        _ => {
+            let top = halves(rows[0]);
+            let bottom = halves(rows[1]);
+            vec![top[0], top[1], bottom[0], bottom[1]]
+        }
+    }
+}
+
+/// A rectangle of the given percentage size centered in `area`.
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+/// Actions advertised in the footer; the rest are listed in the help overlay.
+const FOOTER_ACTIONS: [Action; 8] = [
+    Action::Quit,
+    Action::Start,
+    Action::Stop,
+    Action::Restart,
+    Action::Search,
+    Action::Filter,
+    Action::SplitPane,
+    Action::Help,
+];
+
+/// Keys handled outside the keymap, shown at the end of the help overlay.
+const INPUT_KEYS: [(&str, &str); 5] = [
+    ("Enter", "Apply search or filter"),
+    ("Esc", "Cancel input or close popup"),
+    ("Wheel", "Scroll logs or move the selection"),
+    ("Click", "Select service (twice to start/stop) or focus pane"),
+    ("Drag", "Select text to copy, or resize columns by their borders"),
+];
+
+fn help_lines(keymap: &Keymap) -> Vec<Line<'static>> {
+    let key_width = Action::ALL
+        .iter()
+        .map(|action| keymap.describe(*action).chars().count())
+        .chain(INPUT_KEYS.iter().map(|(keys, _)| keys.chars().count()))
+        .max()
+        .unwrap_or(0);
+    let heading = Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD);
+    let key_style = Style::default().fg(Color::Yellow);
+    let entry = |keys: String, description: &str| {
+        Line::from(vec![
+            Span::styled(format!("  {:<width$}  ", keys, width = key_width), key_style),
+            Span::raw(description.to_string()),
+        ])
+    };
+
+    let mut lines = Vec::new();
+    for context in Context::ALL {
+        if !lines.is_empty() {
+            lines.push(Line::default());
+        }
+        lines.push(Line::styled(context.title(), heading));
+        for action in Action::ALL.iter().filter(|a| a.context() == context) {
+            lines.push(entry(keymap.describe(*action), action.description()));
+        }
+    }
+    lines.push(Line::default());
+    lines.push(Line::styled("Input & mouse", heading));
+    for (keys, description) in INPUT_KEYS {
+        lines.push(entry(keys.to_string(), description));
+    }
+    lines
+}
+
+/// One palette entry with the matched characters highlighted; `indices`
+/// count chars across `text`, a separating space and `detail`.
+fn palette_line(candidate: &Candidate, indices: &[usize]) -> Line<'static> {
+    let matched = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let detail_start = candidate.text.chars().count() + 1;
+    let mut spans = Vec::new();
+    for (i, ch) in candidate.text.chars().enumerate() {
+        let style = if indices.contains(&i) { matched } else { Style::default() };
+        spans.push(Span::styled(ch.to_string(), style));
+    }
+    spans.push(Span::raw("  "));
+    for (i, ch) in candidate.detail.chars().enumerate() {
+        let style = if indices.contains(&(detail_start + i)) { matched } else { Style::default().fg(Color::DarkGray) };
+        spans.push(Span::styled(ch.to_string(), style));
+    }
+    Line::from(spans)
+}
+
+fn draw_palette(f: &mut Frame, area: Rect, palette: &Palette) {
+    let title = match palette.kind {
+        PaletteKind::Services => " FIND SERVICE ",
+        PaletteKind::Commands => " COMMANDS ",
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Cyan))
+        .title(title)
+        .title(Line::from(format!(" {}/{} ", palette.matches.len(), palette.candidates.len())).right_aligned())
+        .title_bottom(" [Up/Down] Select | [Enter] Go | [Esc] Close ");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    let prompt = if palette.kind == PaletteKind::Commands { ":" } else { "> " };
+    f.render_widget(Paragraph::new(format!("{}{}█", prompt, palette.query)), chunks[0]);
+
+    let items: Vec<ListItem> = palette
+        .matches
+        .iter()
+        .map(|m| ListItem::new(palette_line(&palette.candidates[m.candidate], &m.indices)))
+        .collect();
+    let list = List::new(items).highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD));
+    let mut state = ListState::default().with_selected(Some(palette.selected));
+    f.render_stateful_widget(list, chunks[1], &mut state);
+}
+
+fn draw_popup(f: &mut Frame, popup: &Popup, keymap: &Keymap) {
+    let area = match popup {
+        Popup::PortConflict { .. } => centered_rect(50, 30, f.area()),
+        _ => centered_rect(60, 60, f.area()),
+    };
+    f.render_widget(Clear, area);
+    match popup {
+        Popup::Runs { service, runs, selected } => {
+            let items: Vec<ListItem> = runs
+                .iter()
+                .map(|run| {
+                    ListItem::new(format!(
+                        "{}  {:>10}  {}",
+                        run.modified.format("%Y-%m-%d %H:%M:%S"),
+                        format_bytes(run.size as usize),
+                        run.file_name
+                    ))
+                })
+                .collect();
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(Color::Cyan))
+                .title(format!(" PAST RUNS: {} ", service))
+                .title_bottom(" [Enter] Open | [Esc] Close ");
+            let list = List::new(items)
+                .block(block)
+                .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+            let mut state = ListState::default().with_selected(Some(*selected));
+            f.render_stateful_widget(list, area, &mut state);
+        }
+        Popup::Palette(palette) => draw_palette(f, area, palette),
+        Popup::Help { scroll } => {
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(Color::Cyan))
+                .title(" KEYBINDINGS ")
+                .title_bottom(" [J/K] Scroll | [Any key] Close ");
+            let paragraph = Paragraph::new(help_lines(keymap))
+                .block(block)
+                .scroll((*scroll, 0));
+            f.render_widget(paragraph, area);
+        }
+        Popup::ConfigDiff { changes, scroll } => {
+            let mut lines = Vec::new();
+            for change in changes {
+                let (sign, color) = match change.kind {
+                    ChangeKind::Added => ("+", Color::Green),
+                    ChangeKind::Removed => ("-", Color::Red),
+                    ChangeKind::Modified => ("~", Color::Yellow),
+                };
+                let mut spans = vec![Span::styled(format!("{} {}", sign, change.service), Style::default().fg(color).add_modifier(Modifier::BOLD))];
+                if change.needs_restart {
+                    spans.push(Span::styled("  (restart to apply)", Style::default().fg(Color::DarkGray)));
+                }
+                lines.push(Line::from(spans));
+                for detail in &change.details {
+                    lines.push(Line::from(format!("    {}", detail)));
+                }
+            }
+            let hints = if changes.iter().any(|c| c.needs_restart) {
+                " [R] Restart changed services | [J/K] Scroll | [Any key] Close "
+            } else {
+                " [J/K] Scroll | [Any key] Close "
+            };
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(Color::Cyan))
+                .title(" CONFIG RELOADED ")
+                .title_bottom(hints);
+            f.render_widget(Paragraph::new(lines).block(block).scroll((*scroll, 0)), area);
+        }
+        Popup::PortConflict { port, holder, owner, .. } => {
+            let mut hints = vec!["[S] Start anyway", "[Esc] Cancel"];
+            if holder.pid != 0 {
+                hints.insert(0, "[K] Kill holder and start");
+            }
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title(format!(" PORT {} IN USE ", port))
+                .title_bottom(format!(" {} ", hints.join(" | ")));
+            let text = vec![
+                Line::from(""),
+                Line::from(format!("{} is already listening on port {}.", describe_holder(holder, owner.as_deref()), port)),
+            ];
+            f.render_widget(Paragraph::new(text).block(block).wrap(Wrap { trim: false }), area);
+        }
+        Popup::Detail { title, lines, scroll } => {
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(Color::Cyan))
+                .title(format!(" {} ", title))
+                .title_bottom(" [J/K] Scroll | [Esc] Close ");
+            let text: Vec<Line> = lines.iter().map(|l| Line::from(l.as_str())).collect();
+            let paragraph = Paragraph::new(text)
+                .block(block)
+                .wrap(Wrap { trim: false })
+                .scroll((*scroll, 0));
+            f.render_widget(paragraph, area);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Width of the `HH:MM:SS out       ` prefix of unleveled lines.
+    const PREFIX: usize = 19;
+    const WIDTH: usize = PREFIX + 10;
+
+    fn ctx() -> PaneContext<'static> {
+        PaneContext {
+            search: "",
+            text_color: Color::White,
+            dimmed_color: Color::Gray,
+            service_colors: None,
+            muted: None,
+            empty_hint: "",
+        }
+    }
+
+    /// A buffer of lines whose text wraps to `rows[i]` rows at `WIDTH`.
+    fn buffer(max_lines: usize, rows: &[usize]) -> LogBuffer {
+        let mut buffer = LogBuffer::new(max_lines, usize::MAX);
+        for &n in rows {
+            push(&mut buffer, n);
+        }
+        buffer
+    }
+
+    fn push(buffer: &mut LogBuffer, rows: usize) {
+        let text = "x".repeat(rows * WIDTH - PREFIX);
+        buffer.push(LogLine::new("web", LogStream::Stdout, text));
+    }
+
+    fn index(buffer: &LogBuffer, filter: &LogFilter, width: usize) -> RowIndex {
+        let mut rows = RowIndex::default();
+        rows.update(buffer, filter, &ctx(), width);
+        rows
+    }
+
+    #[test]
+    fn wrap_rows_counts_display_width() {
+        assert_eq!(wrap_rows("abcdef", 4), [0..4, 4..6]);
+        // Each of these takes two columns and three bytes.
+        assert_eq!(wrap_rows("日本語", 4), [0..6, 6..9]);
+        assert_eq!(wrap_rows("", 4), vec![0..0]);
+        assert_eq!(wrap_rows("abc", 0), vec![0..3]);
+    }
+
+    #[test]
+    fn rows_are_indexed_per_line() {
+        let rows = index(&buffer(10, &[1, 3, 2]), &LogFilter::default(), WIDTH);
+        assert_eq!(rows.total(), 6);
+        assert_eq!((rows.row_of(0), rows.row_of(1), rows.row_of(2)), (0, 1, 4));
+        assert_eq!(rows.line_at(0), Some((0, 0)));
+        assert_eq!(rows.line_at(3), Some((1, 2)));
+        assert_eq!(rows.line_at(5), Some((2, 1)));
+        assert_eq!(rows.line_at(6), None);
+    }
+
+    #[test]
+    fn only_new_lines_are_indexed_and_evicted_ones_forgotten() {
+        let mut buffer = buffer(3, &[1, 2, 1]);
+        let filter = LogFilter::default();
+        let mut rows = index(&buffer, &filter, WIDTH);
+        assert_eq!(rows.total(), 4);
+        push(&mut buffer, 2);
+        push(&mut buffer, 1);
+        rows.update(&buffer, &filter, &ctx(), WIDTH);
+        // Lines 0 and 1 were evicted; 2, 3 and 4 remain.
+        assert_eq!(rows.total(), 4);
+        assert!(!rows.contains(1));
+        assert!(rows.contains(2));
+        assert_eq!(rows.row_of(0), 0);
+        assert_eq!(rows.row_of(3), 1);
+        assert_eq!(rows.line_at(3), Some((2, 0)));
+        // Same as indexing from scratch.
+        let fresh = index(&buffer, &filter, WIDTH);
+        assert_eq!(rows.total(), fresh.total());
+        assert_eq!(rows.row_of(4), fresh.row_of(4));
+    }
+
+    #[test]
+    fn changing_the_width_or_filter_rebuilds_the_index() {
+        let buffer = buffer(10, &[1, 2]);
+        let mut rows = index(&buffer, &LogFilter::default(), WIDTH);
+        rows.update(&buffer, &LogFilter::default(), &ctx(), WIDTH * 3);
+        assert_eq!(rows.total(), 2);
+        let only_stderr = LogFilter::parse("", StreamFilter::Stderr).expect("valid filter");
+        rows.update(&buffer, &only_stderr, &ctx(), WIDTH);
+        assert_eq!(rows.total(), 0);
+        assert!(!rows.contains(0));
+        assert_eq!(rows.line_at(0), None);
+    }
+
+    #[test]
+    fn merged_views_count_the_service_label() {
+        let colors = HashMap::from([("web".to_string(), Color::Red)]);
+        let labeled = PaneContext { service_colors: Some(&colors), ..ctx() };
+        let buffer = buffer(10, &[1]);
+        let mut rows = RowIndex::default();
+        rows.update(&buffer, &LogFilter::default(), &labeled, WIDTH);
+        // `web │ ` pushes the full-width line onto a second row.
+        assert_eq!(rows.total(), 2);
+        let muted = HashSet::from(["web".to_string()]);
+        rows.update(&buffer, &LogFilter::default(), &PaneContext { muted: Some(&muted), ..labeled }, WIDTH);
+        assert_eq!(rows.total(), 0);
+    }
 }
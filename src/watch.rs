@@ -0,0 +1,328 @@
+use crate::config::WatchConfig;
+use crate::events::Event;
+use crate::models::Service;
+use crate::scanner;
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc::UnboundedSender;
+use walkdir::WalkDir;
+
+/// Never worth restarting for, whatever the globs say.
+const DEFAULT_IGNORE: [&str; 4] = ["**/.git/**", "**/node_modules/**", "**/target/**", "**/.devrunner/**"];
+
+type FileWatcher = Debouncer<RecommendedWatcher>;
+
+/// A service's file watcher, with what it was started for so that a rescan
+/// can keep it when neither changed.
+pub struct ServiceWatcher {
+    dir: PathBuf,
+    config: WatchConfig,
+    _debouncer: FileWatcher,
+}
+
+impl ServiceWatcher {
+    pub fn is_for(&self, service: &Service, config: &WatchConfig) -> bool {
+        self.dir == service.path && self.config == *config
+    }
+}
+
+fn glob_set<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).with_context(|| format!("Invalid watch glob {:?}", pattern))?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Tells real changes from files merely being opened, which inotify reports
+/// too: a file counts as changed when its modification time differs from
+/// when it was last seen, or, the first time, is later than the watcher.
+struct Modified {
+    since: SystemTime,
+    seen: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl Modified {
+    fn new() -> Self {
+        Self { since: SystemTime::now(), seen: HashMap::new() }
+    }
+
+    fn changed(&mut self, path: &Path) -> bool {
+        let mtime = fs::metadata(path).and_then(|m| m.modified()).ok();
+        match self.seen.insert(path.to_path_buf(), mtime) {
+            Some(previous) => previous != mtime,
+            // Gone, or written since watching started.
+            None => mtime.is_none_or(|t| t >= self.since),
+        }
+    }
+}
+
+/// The directory a glob can match under: its components up to the first one
+/// containing a wildcard.
+fn literal_prefix(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[', '{']))
+        .collect()
+}
+
+/// Watch the files matched by `config` under the service's directory and send
+/// `Event::FilesChanged` with the changed paths, relative to that directory,
+/// once changes settle.
+pub fn watch(service: &Service, config: &WatchConfig, tx: UnboundedSender<Event>) -> Result<ServiceWatcher> {
+    let include = glob_set(config.paths.iter().map(String::as_str))?;
+    let ignore = glob_set(DEFAULT_IGNORE.into_iter().chain(config.ignore.iter().map(String::as_str)))?;
+
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for pattern in &config.paths {
+        let mut root = service.path.join(literal_prefix(pattern));
+        // A single file: watch its directory so it is seen when replaced.
+        while !root.is_dir() && root != service.path {
+            root.pop();
+        }
+        if !roots.iter().any(|r| root.starts_with(r)) {
+            roots.retain(|r| !r.starts_with(&root));
+            roots.push(root);
+        }
+    }
+
+    let name = service.name.clone();
+    let dir = service.path.clone();
+    let mut modified = Modified::new();
+    let mut debouncer = new_debouncer(Duration::from_millis(config.debounce_ms), move |result: DebounceEventResult| {
+        let Ok(events) = result else {
+            return;
+        };
+        let mut changed: Vec<PathBuf> = events
+            .into_iter()
+            .filter_map(|e| {
+                let rel = e.path.strip_prefix(&dir).ok()?;
+                (include.is_match(rel) && !ignore.is_match(rel) && modified.changed(&e.path)).then(|| rel.to_path_buf())
+            })
+            .collect();
+        changed.sort();
+        changed.dedup();
+        if !changed.is_empty() {
+            let _ = tx.send(Event::FilesChanged(name.clone(), changed));
+        }
+    })?;
+    for root in &roots {
+        debouncer
+            .watcher()
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("Cannot watch {}", root.display()))?;
+    }
+    Ok(ServiceWatcher { dir: service.path.clone(), config: config.clone(), _debouncer: debouncer })
+}
+
+/// Directories that never hold manifests worth scanning.
+fn skipped_dir(name: &std::ffi::OsStr) -> bool {
+    [".git", ".devrunner", "node_modules", "target"].iter().any(|n| name == *n)
+}
+
+/// Whether `dir` may hold manifests: at most two levels below `root`, and
+/// not inside a directory the scanner skips.
+fn scanned_dir(root: &Path, dir: &Path) -> bool {
+    dir.strip_prefix(root)
+        .is_ok_and(|rel| rel.components().count() <= 2 && !rel.components().any(|c| skipped_dir(c.as_os_str())))
+}
+
+/// Watches the directories the scanner looks in, plus those of the config
+/// files, and sends `Event::ProjectChanged` when a manifest or a config file
+/// changes, or a directory that may hold manifests is added or removed.
+/// Anything else, like editor swap files or build output, is ignored.
+pub struct ProjectWatcher {
+    debouncer: FileWatcher,
+    root: PathBuf,
+    config_files: Vec<PathBuf>,
+    /// Directories being watched, shared with the event handler to tell new
+    /// and removed directories from changes inside known ones.
+    watched: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+impl ProjectWatcher {
+    /// `config_files` are every path a config file may be at, so that one
+    /// created later is noticed too.
+    pub fn new(root: &Path, config_files: Vec<PathBuf>, tx: UnboundedSender<Event>) -> Result<Self> {
+        let configs = config_files.clone();
+        let watched: Arc<Mutex<HashSet<PathBuf>>> = Arc::default();
+        let known = watched.clone();
+        let project = root.to_path_buf();
+        let mut modified = Modified::new();
+        let debouncer = new_debouncer(Duration::from_millis(300), move |result: DebounceEventResult| {
+            let Ok(events) = result else {
+                return;
+            };
+            let known = known.lock().unwrap_or_else(|e| e.into_inner());
+            let mut changed: Vec<PathBuf> = events
+                .into_iter()
+                .map(|e| e.path)
+                .filter(|p| {
+                    ((configs.contains(p) || scanner::is_manifest(p)) && modified.changed(p))
+                        || if known.contains(p) {
+                            !p.exists()
+                        } else {
+                            p.is_dir() && scanned_dir(&project, p)
+                        }
+                })
+                .collect();
+            drop(known);
+            changed.sort();
+            changed.dedup();
+            if !changed.is_empty() {
+                let _ = tx.send(Event::ProjectChanged(changed));
+            }
+        })?;
+        let mut watcher = Self {
+            debouncer,
+            root: root.to_path_buf(),
+            config_files,
+            watched,
+        };
+        watcher.refresh()?;
+        Ok(watcher)
+    }
+
+    /// Follow directories added or removed since the last call.
+    pub fn refresh(&mut self) -> Result<()> {
+        // Manifests are found up to three levels down, so in directories
+        // up to two levels down.
+        let mut dirs: HashSet<PathBuf> = WalkDir::new(&self.root)
+            .max_depth(2)
+            .follow_links(true)
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !skipped_dir(e.file_name()))
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir())
+            .map(|e| e.into_path())
+            .collect();
+        dirs.extend(self.config_files.iter().filter_map(|f| f.parent()).filter(|d| d.is_dir()).map(Path::to_path_buf));
+        let mut watched = self.watched.lock().unwrap_or_else(|e| e.into_inner());
+        for gone in watched.difference(&dirs) {
+            let _ = self.debouncer.watcher().unwatch(gone);
+        }
+        for dir in dirs.difference(&watched) {
+            self.debouncer
+                .watcher()
+                .watch(dir, RecursiveMode::NonRecursive)
+                .with_context(|| format!("Cannot watch {}", dir.display()))?;
+        }
+        *watched = dirs;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProjectType;
+    use std::fs::File;
+    use tokio::sync::mpsc;
+
+    fn set_mtime(path: &Path, mtime: SystemTime) {
+        File::options().write(true).open(path).and_then(|f| f.set_modified(mtime)).expect("set mtime");
+    }
+
+    #[test]
+    fn files_count_as_modified_only_when_their_mtime_moves() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let old = dir.path().join("old.rs");
+        let new = dir.path().join("new.rs");
+        fs::write(&old, "").expect("write");
+        set_mtime(&old, SystemTime::now() - Duration::from_secs(60));
+
+        let mut modified = Modified::new();
+        // Opened, not written: inotify reports it anyway.
+        assert!(!modified.changed(&old));
+        assert!(!modified.changed(&old));
+        set_mtime(&old, SystemTime::now());
+        assert!(modified.changed(&old));
+        assert!(!modified.changed(&old));
+
+        // Created since watching started.
+        fs::write(&new, "").expect("write");
+        set_mtime(&new, modified.since + Duration::from_secs(1));
+        assert!(modified.changed(&new));
+        fs::remove_file(&new).expect("remove");
+        assert!(modified.changed(&new));
+        assert!(!modified.changed(&new));
+    }
+
+    #[test]
+    fn literal_prefix_stops_at_the_first_wildcard() {
+        assert_eq!(literal_prefix("src/**/*.rs"), Path::new("src"));
+        assert_eq!(literal_prefix("config/app.{json,toml}"), Path::new("config"));
+        assert_eq!(literal_prefix("Cargo.toml"), Path::new("Cargo.toml"));
+        assert_eq!(literal_prefix("**/*.ts"), Path::new(""));
+        assert!(glob_set(["src/[.rs"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn matching_writes_are_reported_relative_to_the_service() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let root = dir.path().canonicalize().expect("canonical temp dir");
+        fs::create_dir_all(root.join("src")).expect("create src");
+        fs::create_dir_all(root.join("node_modules")).expect("create node_modules");
+        let service = Service::new("web: dev".to_string(), root.clone(), root.join("package.json"), ProjectType::Node, String::new());
+        let config = WatchConfig { paths: vec!["src/**/*.ts".to_string(), "**/*.json".to_string()], ignore: vec!["src/gen/**".to_string()], debounce_ms: 50 };
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let watcher = watch(&service, &config, tx).expect("watching");
+        assert!(watcher.is_for(&service, &config));
+
+        fs::create_dir_all(root.join("src/gen")).expect("create gen");
+        fs::write(root.join("src/gen/types.ts"), "").expect("write ignored");
+        fs::write(root.join("src/readme.md"), "").expect("write unmatched");
+        fs::write(root.join("node_modules/pkg.json"), "").expect("write ignored");
+        fs::write(root.join("src/main.ts"), "").expect("write");
+        let event = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.expect("change reported");
+        let Some(Event::FilesChanged(name, paths)) = event else {
+            panic!("unexpected event: {:?}", event);
+        };
+        assert_eq!(name, "web: dev");
+        assert_eq!(paths, [PathBuf::from("src/main.ts")]);
+    }
+
+    #[test]
+    fn project_dirs_are_scanned_two_levels_down_outside_skipped_ones() {
+        let root = Path::new("/p");
+        assert!(scanned_dir(root, root));
+        assert!(scanned_dir(root, &root.join("apps")));
+        assert!(scanned_dir(root, &root.join("apps/web")));
+        assert!(!scanned_dir(root, &root.join("apps/web/src")));
+        assert!(!scanned_dir(root, &root.join("node_modules")));
+        assert!(!scanned_dir(root, &root.join("apps/target")));
+        assert!(!scanned_dir(root, &root.join(".git")));
+        assert!(!scanned_dir(root, Path::new("/elsewhere")));
+    }
+
+    #[tokio::test]
+    async fn new_dirs_and_manifest_writes_change_the_project() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let root = dir.path().canonicalize().expect("canonical temp dir");
+        fs::create_dir_all(root.join("api")).expect("create api");
+        fs::write(root.join("api/package.json"), "{}").expect("write manifest");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let _watcher = ProjectWatcher::new(&root, vec![root.join(".devrunner.json")], tx).expect("watching");
+        let next = async |rx: &mut mpsc::UnboundedReceiver<Event>| {
+            match tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.expect("change reported") {
+                Some(Event::ProjectChanged(paths)) => paths,
+                event => panic!("unexpected event: {:?}", event),
+            }
+        };
+
+        // Editor swap files next to a manifest are not a change.
+        fs::write(root.join("api/.package.json.swp"), "").expect("write swap file");
+        fs::write(root.join("api/package.json"), r#"{"scripts":{}}"#).expect("write manifest");
+        assert_eq!(next(&mut rx).await, [root.join("api/package.json")]);
+
+        fs::create_dir(root.join("web")).expect("create web");
+        assert_eq!(next(&mut rx).await, [root.join("web")]);
+    }
+}